@@ -0,0 +1,139 @@
+mod airnow;
+mod open_meteo;
+
+use crate::{
+    air_quality::{airnow::AirNow, open_meteo::OpenMeteo},
+    config::Config,
+    state::{Message, Tx},
+};
+use serde::Deserialize;
+use std::{
+    fmt::{self, Display},
+    thread,
+    time::Duration,
+};
+use tracing::error;
+
+/// Time between requests. Most sources only update hourly
+const DATA_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Fetch air quality in a loop. When we get new data, send a message to
+/// update state. If air quality isn't configured, there's nothing to do
+pub fn air_quality_loop(config: Config, tx: Tx) {
+    let Some(air_quality_config) = &config.air_quality else {
+        return;
+    };
+    // Both providers look up readings by location
+    let Some(coordinates) = config.coordinates() else {
+        error!("Air quality needs `coordinates` in the config");
+        return;
+    };
+    let provider: Box<dyn AirQualityProvider> = match air_quality_config {
+        AirQualityConfig::AirNow { api_key, host } => {
+            Box::new(AirNow::new(host, api_key, coordinates))
+        }
+        AirQualityConfig::OpenMeteo { host } => {
+            Box::new(OpenMeteo::new(host, coordinates))
+        }
+    };
+
+    loop {
+        if let Ok(air_quality) = provider.fetch() {
+            tx.send(Message::AirQuality(air_quality));
+        }
+        thread::sleep(DATA_TTL);
+    }
+}
+
+/// Where to fetch air quality data from. The host can be overridden, e.g. to
+/// point at a local stub server
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum AirQualityConfig {
+    /// EPA AirNow (US only). No pollen data
+    AirNow {
+        api_key: String,
+        #[serde(default = "AirNow::default_host")]
+        host: String,
+    },
+    /// Open-Meteo (global). Pollen is only available in Europe
+    OpenMeteo {
+        #[serde(default = "OpenMeteo::default_host")]
+        host: String,
+    },
+}
+
+/// A source of air quality data
+pub trait AirQualityProvider {
+    /// Fetch current conditions and tomorrow's forecast. Errors are logged by
+    /// the provider
+    fn fetch(&self) -> Result<AirQuality, ()>;
+}
+
+/// Air quality and pollen, converted from whichever provider it came from
+#[derive(Clone, Debug, Default)]
+pub struct AirQuality {
+    /// Most recent observation
+    pub current: Option<AqiReading>,
+    /// Forecast for tomorrow
+    pub tomorrow: Option<AqiReading>,
+    /// Pollen counts for each type that the provider reports, highest first
+    pub pollen: Vec<PollenReading>,
+}
+
+/// US EPA Air Quality Index for a point/period in time
+#[derive(Clone, Debug)]
+pub struct AqiReading {
+    /// Index value. Some forecasts only provide the category
+    pub aqi: Option<u32>,
+    pub category: AqiCategory,
+    /// Pollutant with the highest index, which determines the overall index
+    pub pollutant: String,
+}
+
+/// https://www.airnow.gov/aqi/aqi-basics/
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AqiCategory {
+    Good,
+    Moderate,
+    UnhealthyForSensitive,
+    Unhealthy,
+    VeryUnhealthy,
+    Hazardous,
+}
+
+impl AqiCategory {
+    /// Get the category for an index value
+    pub fn from_aqi(aqi: u32) -> Self {
+        match aqi {
+            0..=50 => Self::Good,
+            51..=100 => Self::Moderate,
+            101..=150 => Self::UnhealthyForSensitive,
+            151..=200 => Self::Unhealthy,
+            201..=300 => Self::VeryUnhealthy,
+            _ => Self::Hazardous,
+        }
+    }
+}
+
+impl Display for AqiCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Good => write!(f, "Good"),
+            Self::Moderate => write!(f, "Moderate"),
+            Self::UnhealthyForSensitive => write!(f, "Sensitive"),
+            Self::Unhealthy => write!(f, "Unhealthy"),
+            Self::VeryUnhealthy => write!(f, "Very Unhlthy"),
+            Self::Hazardous => write!(f, "Hazardous"),
+        }
+    }
+}
+
+/// Concentration of a single type of pollen
+#[derive(Clone, Debug)]
+pub struct PollenReading {
+    /// e.g. "Grass"
+    pub name: &'static str,
+    /// Grains per cubic meter
+    pub grains: f64,
+}
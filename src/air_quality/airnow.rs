@@ -0,0 +1,212 @@
+use crate::{
+    air_quality::{AirQuality, AirQualityProvider, AqiCategory, AqiReading},
+    config::Coordinates,
+    util::http_get,
+};
+use chrono::{Local, NaiveDate, TimeDelta};
+use serde::Deserialize;
+
+/// Max distance from the coordinates to look for a reporting area, in miles
+const DISTANCE: u32 = 25;
+
+/// EPA AirNow. US only
+///
+/// https://docs.airnowapi.org/webservices
+#[derive(Debug)]
+pub struct AirNow {
+    current_url: String,
+    forecast_url: String,
+}
+
+impl AirNow {
+    pub fn default_host() -> String {
+        "https://www.airnowapi.org".into()
+    }
+
+    pub fn new(host: &str, api_key: &str, coordinates: Coordinates) -> Self {
+        let query = format!(
+            "format=application/json&latitude={}&longitude={}\
+            &distance={DISTANCE}&API_KEY={api_key}",
+            coordinates.latitude, coordinates.longitude,
+        );
+        Self {
+            current_url: format!(
+                "{host}/aq/observation/latLong/current/?{query}"
+            ),
+            forecast_url: format!("{host}/aq/forecast/latLong/?{query}"),
+        }
+    }
+}
+
+impl AirQualityProvider for AirNow {
+    fn fetch(&self) -> Result<AirQuality, ()> {
+        // Either one is worth showing without the other
+        let current = http_get::<Vec<ApiReading>>(&self.current_url);
+        let forecast = http_get::<Vec<ApiReading>>(&self.forecast_url);
+        if current.is_err() && forecast.is_err() {
+            return Err(());
+        }
+
+        // Observations with no index mean the monitor didn't report
+        let current = current.unwrap_or_default();
+        let current = current.iter().filter(|reading| reading.aqi >= 0);
+        let tomorrow = Local::now().date_naive() + TimeDelta::days(1);
+        let forecast = forecast.unwrap_or_default();
+        let tomorrow = forecast
+            .iter()
+            .filter(|reading| reading.date_forecast == Some(tomorrow));
+        Ok(AirQuality {
+            current: dominant(current),
+            tomorrow: dominant(tomorrow),
+            pollen: Vec::new(),
+        })
+    }
+}
+
+/// AirNow gives one reading per pollutant. The overall index is the highest
+/// of those. Readings without a known category are ignored
+fn dominant<'a>(
+    readings: impl Iterator<Item = &'a ApiReading>,
+) -> Option<AqiReading> {
+    readings
+        .filter_map(|reading| Some((reading, reading.category.category()?)))
+        .max_by_key(|(reading, category)| (*category, reading.aqi))
+        .map(|(reading, category)| AqiReading {
+            // -1 indicates the forecast only has a category
+            aqi: u32::try_from(reading.aqi).ok(),
+            category,
+            pollutant: reading.parameter_name.clone(),
+        })
+}
+
+/// An observation or forecast for a single pollutant
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ApiReading {
+    /// Only present for forecasts
+    #[serde(default, with = "date_format")]
+    date_forecast: Option<NaiveDate>,
+    /// e.g. "PM2.5" or "O3"
+    parameter_name: String,
+    #[serde(rename = "AQI")]
+    aqi: i32,
+    category: ApiCategory,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ApiCategory {
+    /// 1 (Good) through 6 (Hazardous), or 7 (Unavailable)
+    number: u8,
+}
+
+impl ApiCategory {
+    fn category(&self) -> Option<AqiCategory> {
+        match self.number {
+            1 => Some(AqiCategory::Good),
+            2 => Some(AqiCategory::Moderate),
+            3 => Some(AqiCategory::UnhealthyForSensitive),
+            4 => Some(AqiCategory::Unhealthy),
+            5 => Some(AqiCategory::VeryUnhealthy),
+            6 => Some(AqiCategory::Hazardous),
+            _ => None,
+        }
+    }
+}
+
+/// AirNow dates look like "2024-10-19 ", trailing space and all
+mod date_format {
+    use chrono::NaiveDate;
+    use serde::{Deserialize, Deserializer};
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<NaiveDate>, D::Error> {
+        let date = String::deserialize(deserializer)?;
+        NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+            .map(Some)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::stub_server;
+    use serde_json::json;
+
+    const COORDINATES: Coordinates = Coordinates {
+        latitude: 42.36,
+        longitude: -71.06,
+    };
+
+    fn reading(parameter: &str, aqi: i32, category: u8) -> serde_json::Value {
+        json!({
+            "ParameterName": parameter,
+            "AQI": aqi,
+            "Category": {"Number": category, "Name": "whatever"},
+        })
+    }
+
+    /// Forecast readings have a date, with AirNow's trailing space
+    fn forecast(
+        date: NaiveDate,
+        parameter: &str,
+        aqi: i32,
+        category: u8,
+    ) -> serde_json::Value {
+        let mut reading = reading(parameter, aqi, category);
+        reading["DateForecast"] =
+            format!("{} ", date.format("%Y-%m-%d")).into();
+        reading
+    }
+
+    #[test]
+    fn test_fetch() {
+        let today = Local::now().date_naive();
+        let tomorrow = today + TimeDelta::days(1);
+        let current = json!([
+            reading("O3", 30, 1),
+            reading("PM2.5", 55, 2),
+            // Unavailable shouldn't count as the worst category
+            reading("PM10", -1, 7),
+        ]);
+        let forecasts = json!([
+            forecast(today, "PM2.5", 120, 3),
+            forecast(tomorrow, "O3", -1, 2),
+            forecast(tomorrow, "PM2.5", 40, 1),
+        ]);
+        let host = stub_server::serve(vec![
+            ("/aq/observation/", 200, current.to_string()),
+            ("/aq/forecast/", 200, forecasts.to_string()),
+        ]);
+
+        let air_quality =
+            AirNow::new(&host, "secret", COORDINATES).fetch().unwrap();
+        let current = air_quality.current.unwrap();
+        assert_eq!(current.aqi, Some(55));
+        assert_eq!(current.category, AqiCategory::Moderate);
+        assert_eq!(current.pollutant, "PM2.5");
+        let tomorrow = air_quality.tomorrow.unwrap();
+        assert_eq!(tomorrow.aqi, None);
+        assert_eq!(tomorrow.category, AqiCategory::Moderate);
+        assert_eq!(tomorrow.pollutant, "O3");
+    }
+
+    /// A failed forecast doesn't throw away the current reading
+    #[test]
+    fn test_fetch_forecast_error() {
+        let current = json!([reading("O3", 30, 1)]);
+        let host = stub_server::serve(vec![
+            ("/aq/observation/", 200, current.to_string()),
+            ("/aq/forecast/", 500, String::new()),
+        ]);
+        let air_quality =
+            AirNow::new(&host, "secret", COORDINATES).fetch().unwrap();
+        assert_eq!(air_quality.current.unwrap().aqi, Some(30));
+        assert!(air_quality.tomorrow.is_none());
+
+        let host = stub_server::serve(vec![]);
+        assert!(AirNow::new(&host, "secret", COORDINATES).fetch().is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use crate::{
+    air_quality::{
+        AirQuality, AirQualityProvider, AqiCategory, AqiReading, PollenReading,
+    },
+    config::Coordinates,
+    util::http_get,
+};
+use chrono::{DateTime, Local, TimeDelta};
+use itertools::Itertools;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Pollutants that have their own US AQI sub-index, as (API name, display
+/// name). The overall index is the highest of these
+const POLLUTANTS: [(&str, &str); 6] = [
+    ("us_aqi_pm2_5", "PM2.5"),
+    ("us_aqi_pm10", "PM10"),
+    ("us_aqi_ozone", "O3"),
+    ("us_aqi_nitrogen_dioxide", "NO2"),
+    ("us_aqi_sulphur_dioxide", "SO2"),
+    ("us_aqi_carbon_monoxide", "CO"),
+];
+/// Pollen types, as (API name, display name)
+const POLLEN: [(&str, &str); 6] = [
+    ("alder_pollen", "Alder"),
+    ("birch_pollen", "Birch"),
+    ("grass_pollen", "Grass"),
+    ("mugwort_pollen", "Mugwort"),
+    ("olive_pollen", "Olive"),
+    ("ragweed_pollen", "Ragweed"),
+];
+
+/// Open-Meteo air quality. Global coverage, no API key required
+///
+/// https://open-meteo.com/en/docs/air-quality-api
+#[derive(Debug)]
+pub struct OpenMeteo {
+    url: String,
+}
+
+impl OpenMeteo {
+    pub fn default_host() -> String {
+        "https://air-quality-api.open-meteo.com".into()
+    }
+
+    pub fn new(host: &str, coordinates: Coordinates) -> Self {
+        let hourly = POLLUTANTS.iter().map(|(name, _)| name).join(",");
+        let pollen = POLLEN.iter().map(|(name, _)| name).join(",");
+        let url = format!(
+            "{host}/v1/air-quality?latitude={}&longitude={}\
+            &current={hourly},{pollen}&hourly={hourly}\
+            &forecast_days=3&timeformat=unixtime",
+            coordinates.latitude, coordinates.longitude,
+        );
+        Self { url }
+    }
+}
+
+impl AirQualityProvider for OpenMeteo {
+    fn fetch(&self) -> Result<AirQuality, ()> {
+        let response: ApiResponse = http_get(&self.url)?;
+
+        let current =
+            dominant(|name| response.current.get(name).copied().flatten());
+
+        // Daily forecasts aren't available, so take the worst hour of the day
+        let tomorrow = Local::now().date_naive() + TimeDelta::days(1);
+        let tomorrow_hours: Vec<usize> = response
+            .hourly
+            .time
+            .iter()
+            .positions(|time| {
+                DateTime::from_timestamp(*time, 0).is_some_and(|time| {
+                    time.with_timezone(&Local).date_naive() == tomorrow
+                })
+            })
+            .collect();
+        let tomorrow = dominant(|name| {
+            let values = response.hourly.values.get(name)?;
+            tomorrow_hours
+                .iter()
+                .filter_map(|i| values.get(*i).copied().flatten())
+                .max_by(f64::total_cmp)
+        });
+
+        let pollen = POLLEN
+            .iter()
+            .filter_map(|(api_name, name)| {
+                let grains = response.current.get(*api_name).copied()??;
+                Some(PollenReading { name, grains })
+            })
+            .sorted_by(|a, b| b.grains.total_cmp(&a.grains))
+            .collect();
+
+        Ok(AirQuality {
+            current,
+            tomorrow,
+            pollen,
+        })
+    }
+}
+
+/// Build a reading from whichever pollutant has the highest sub-index
+fn dominant(get: impl Fn(&str) -> Option<f64>) -> Option<AqiReading> {
+    let (pollutant, aqi) = POLLUTANTS
+        .iter()
+        .filter_map(|(api_name, name)| Some((*name, get(api_name)?)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    let aqi = aqi.round() as u32;
+    Some(AqiReading {
+        aqi: Some(aqi),
+        category: AqiCategory::from_aqi(aqi),
+        pollutant: pollutant.into(),
+    })
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ApiResponse {
+    /// Most recent value for each requested variable
+    current: HashMap<String, Option<f64>>,
+    hourly: ApiHourly,
+}
+
+/// Hourly data, one array per variable
+#[derive(Clone, Debug, Deserialize)]
+struct ApiHourly {
+    /// Unix timestamps
+    time: Vec<i64>,
+    #[serde(flatten)]
+    values: HashMap<String, Vec<Option<f64>>>,
+}
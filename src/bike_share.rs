@@ -0,0 +1,343 @@
+//! Bike-share dock availability, from any system that publishes a GBFS feed
+//!
+//! <https://gbfs.org/documentation/reference/>
+
+use crate::{
+    config::Config,
+    state::{Message, Tx},
+    util::http_get,
+};
+use serde::{Deserialize, Deserializer};
+use std::{
+    collections::{HashMap, HashSet},
+    thread,
+    time::Duration,
+};
+use tracing::error;
+
+/// Time between requests. Most systems update status every minute
+const DATA_TTL: Duration = Duration::from_secs(60);
+
+/// Fetch station status in a loop. When we get new data, send a message to
+/// update state. If bike share isn't configured, there's nothing to do
+pub fn bike_share_loop(config: Config, tx: Tx) {
+    let Some(bike_share_config) = &config.bike_share else {
+        return;
+    };
+    let mut client = Gbfs::new(bike_share_config);
+    loop {
+        if let Ok(bike_share) = client.fetch() {
+            tx.send(Message::BikeShare(bike_share));
+        }
+        thread::sleep(DATA_TTL);
+    }
+}
+
+/// Which bike-share system and stations to track
+#[derive(Clone, Debug, Deserialize)]
+pub struct BikeShareConfig {
+    /// Base URL of the system's GBFS feeds, e.g.
+    /// `https://gbfs.bluebikes.com/gbfs/en`
+    pub url: String,
+    pub stations: Vec<BikeStation>,
+}
+
+/// A single dock station to track
+#[derive(Clone, Debug, Deserialize)]
+pub struct BikeStation {
+    /// Display name for the station. Defaults to the system's name for it,
+    /// which is probably too long to fit
+    #[serde(default)]
+    pub name: Option<String>,
+    /// GBFS station ID
+    pub id: String,
+}
+
+/// Availability at each tracked station, ready to be displayed
+#[derive(Clone, Debug, Default)]
+pub struct BikeShare {
+    pub stations: Vec<StationStatus>,
+}
+
+#[derive(Clone, Debug)]
+pub struct StationStatus {
+    pub name: String,
+    /// `None` if the station is missing from the feed
+    pub availability: Option<Availability>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Availability {
+    /// Regular (non-electric) bikes available
+    pub bikes: u32,
+    /// `None` if the system doesn't report e-bikes separately
+    pub ebikes: Option<u32>,
+    /// Empty docks available to return a bike to. `None` if the system
+    /// doesn't say, e.g. because it allows dockless returns
+    pub docks: Option<u32>,
+    /// Is the station renting and accepting returns?
+    pub open: bool,
+}
+
+/// Client for a GBFS feed
+#[derive(Debug)]
+struct Gbfs {
+    url: String,
+    stations: Vec<BikeStation>,
+    /// Station names from the system, by ID. Only fetched once, since
+    /// stations rarely change
+    names: Option<HashMap<String, String>>,
+    /// IDs of stations that were missing from the last fetch, so each one
+    /// is only logged when it goes missing
+    missing: HashSet<String>,
+}
+
+impl Gbfs {
+    fn new(config: &BikeShareConfig) -> Self {
+        Self {
+            url: config.url.trim_end_matches('/').to_owned(),
+            stations: config.stations.clone(),
+            names: None,
+            missing: HashSet::new(),
+        }
+    }
+
+    fn fetch(&mut self) -> Result<BikeShare, ()> {
+        if self.names.is_none() {
+            let url = format!("{}/station_information.json", self.url);
+            // Names are only a fallback, so keep going without them
+            if let Ok(information) = http_get::<Feed<StationInformation>>(&url)
+            {
+                self.names = Some(
+                    information
+                        .data
+                        .stations
+                        .into_iter()
+                        .map(|station| (station.station_id, station.name))
+                        .collect(),
+                );
+            }
+        }
+
+        let url = format!("{}/station_status.json", self.url);
+        let status: Feed<ApiStationStatus> = http_get(&url)?;
+        let mut status: HashMap<String, ApiStationStatus> = status
+            .data
+            .stations
+            .into_iter()
+            .map(|station| (station.station_id.clone(), station))
+            .collect();
+
+        // Only log stations when they go missing, not on every fetch
+        let missing: HashSet<String> = self
+            .stations
+            .iter()
+            .filter(|station| !status.contains_key(&station.id))
+            .map(|station| station.id.clone())
+            .collect();
+        for station_id in missing.difference(&self.missing) {
+            error!("Bike station `{station_id}` not in {url}");
+        }
+        self.missing = missing;
+
+        let stations = self
+            .stations
+            .iter()
+            .map(|station| {
+                let availability =
+                    status.remove(&station.id).map(Availability::from);
+                StationStatus {
+                    name: self.name(station),
+                    availability,
+                }
+            })
+            .collect();
+        Ok(BikeShare { stations })
+    }
+
+    /// Get display name for a station, falling back to the system's name, and
+    /// then the ID
+    fn name(&self, station: &BikeStation) -> String {
+        station
+            .name
+            .clone()
+            .or_else(|| self.names.as_ref()?.get(&station.id).cloned())
+            .unwrap_or_else(|| station.id.clone())
+    }
+}
+
+impl From<ApiStationStatus> for Availability {
+    fn from(status: ApiStationStatus) -> Self {
+        Self {
+            // The bike count includes e-bikes
+            bikes: status
+                .num_bikes_available
+                .saturating_sub(status.num_ebikes_available.unwrap_or(0)),
+            ebikes: status.num_ebikes_available,
+            docks: status.num_docks_available,
+            open: status.is_renting && status.is_returning,
+        }
+    }
+}
+
+/// Every GBFS file wraps its contents the same way
+#[derive(Debug, Deserialize)]
+struct Feed<T> {
+    data: Stations<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Stations<T> {
+    stations: Vec<T>,
+}
+
+/// <https://gbfs.org/documentation/reference/#station_informationjson>
+#[derive(Debug, Deserialize)]
+struct StationInformation {
+    station_id: String,
+    #[serde(deserialize_with = "deserialize_name")]
+    name: String,
+}
+
+/// Names are a plain string in GBFS 2.x, but a list of translations in 3.x.
+/// For translations, just take the first language
+fn deserialize_name<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Name {
+        Plain(String),
+        Localized(Vec<LocalizedString>),
+    }
+
+    #[derive(Deserialize)]
+    struct LocalizedString {
+        text: String,
+    }
+
+    Ok(match Name::deserialize(deserializer)? {
+        Name::Plain(name) => name,
+        Name::Localized(names) => names
+            .into_iter()
+            .next()
+            .map(|name| name.text)
+            .unwrap_or_default(),
+    })
+}
+
+/// <https://gbfs.org/documentation/reference/#station_statusjson>
+#[derive(Debug, Deserialize)]
+struct ApiStationStatus {
+    station_id: String,
+    /// Renamed in GBFS 3.0
+    #[serde(alias = "num_vehicles_available")]
+    num_bikes_available: u32,
+    /// Not in the spec, but many systems (including Bluebikes) include it
+    #[serde(default)]
+    num_ebikes_available: Option<u32>,
+    /// Optional for systems that allow dockless returns
+    #[serde(default)]
+    num_docks_available: Option<u32>,
+    #[serde(deserialize_with = "deserialize_bool")]
+    is_renting: bool,
+    #[serde(deserialize_with = "deserialize_bool")]
+    is_returning: bool,
+}
+
+/// Booleans are `true`/`false` in newer GBFS versions, but `1`/`0` in 1.x
+fn deserialize_bool<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum GbfsBool {
+        Bool(bool),
+        Int(u8),
+    }
+
+    Ok(match GbfsBool::deserialize(deserializer)? {
+        GbfsBool::Bool(value) => value,
+        GbfsBool::Int(value) => value != 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_deserialize_name() {
+        let name = |name| {
+            let information: StationInformation = serde_json::from_value(
+                json!({"station_id": "1", "name": name}),
+            )
+            .unwrap();
+            information.name
+        };
+        // GBFS 2.x
+        assert_eq!(name(json!("Central Sq")), "Central Sq");
+        // GBFS 3.x
+        assert_eq!(
+            name(json!([
+                {"text": "Central Sq", "language": "en"},
+                {"text": "Plaza Central", "language": "es"},
+            ])),
+            "Central Sq"
+        );
+        assert_eq!(name(json!([])), "");
+    }
+
+    fn availability(status: serde_json::Value) -> Availability {
+        let mut station = json!({
+            "station_id": "1",
+            "num_bikes_available": 5,
+            "is_renting": true,
+            "is_returning": true,
+        });
+        station
+            .as_object_mut()
+            .unwrap()
+            .extend(status.as_object().unwrap().clone());
+        Availability::from(
+            serde_json::from_value::<ApiStationStatus>(station).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_deserialize_bool() {
+        assert!(availability(json!({})).open);
+        // GBFS 1.x
+        assert!(availability(json!({"is_renting": 1, "is_returning": 1})).open);
+        assert!(!availability(json!({"is_renting": 0})).open);
+        assert!(!availability(json!({"is_returning": false})).open);
+    }
+
+    #[test]
+    fn test_availability() {
+        let docked = availability(json!({
+            "num_ebikes_available": 2,
+            "num_docks_available": 10,
+        }));
+        // The bike count includes e-bikes
+        assert_eq!(docked.bikes, 3);
+        assert_eq!(docked.ebikes, Some(2));
+        assert_eq!(docked.docks, Some(10));
+
+        let dockless = availability(json!({}));
+        assert_eq!(dockless.bikes, 5);
+        assert_eq!(dockless.ebikes, None);
+        assert_eq!(dockless.docks, None);
+
+        // GBFS 3.x name for the bike count
+        let station = json!({
+            "station_id": "1",
+            "num_vehicles_available": 4,
+            "is_renting": true,
+            "is_returning": true,
+        });
+        let status: ApiStationStatus = serde_json::from_value(station).unwrap();
+        assert_eq!(Availability::from(status).bikes, 4);
+    }
+}
@@ -1,15 +1,58 @@
-use crate::transit::TransitLine;
-use serde::Deserialize;
-use std::fs::File;
+use crate::{
+    air_quality::AirQualityConfig,
+    bike_share::BikeShareConfig,
+    transit::{TransitConfig, TransitLine},
+    units::Units,
+    weather::{WarningConfig, WeatherConfig},
+};
+use serde::{Deserialize, Deserializer, de::Error};
+use std::{fs::File, iter};
 use tracing::info;
 
 /// Global app configuration
 #[derive(Clone, Debug, Deserialize)]
 pub struct Config {
-    pub forecast_office: String,
-    pub forecast_gridpoint: (u32, u32),
+    /// All weather locations, starting with the primary one. Built once from
+    /// several top-level fields; see [deserialize_locations]
+    #[serde(flatten, deserialize_with = "deserialize_locations")]
+    locations: Vec<WeatherLocation>,
+    /// System of measurement for fetched and displayed values
+    #[serde(default)]
+    pub units: Units,
+    /// How to show precipitation on the weather chart
+    #[serde(default)]
+    pub precipitation_display: PrecipitationDisplay,
+    /// Probability of precipitation (0-100) at which the weather summary
+    /// considers precipitation likely
+    #[serde(default = "default_precipitation_threshold")]
+    pub precipitation_threshold: f64,
+    /// Forecast thresholds to warn about, and who to tell
+    #[serde(default)]
+    pub weather_warnings: WarningConfig,
+    /// Glyphs for weather condition icons
+    #[serde(default)]
+    pub weather_icons: IconSet,
+    /// Where to fetch air quality and pollen from. If omitted, the air
+    /// quality mode will be empty
+    #[serde(default)]
+    pub air_quality: Option<AirQualityConfig>,
+    /// Where to fetch transit predictions from
+    #[serde(default)]
+    pub transit: TransitConfig,
     /// Transit lines/stops to be displayed
     pub transit_lines: Vec<TransitLine>,
+    /// Order transit lines, and stops within each line, by soonest
+    /// departure instead of config order
+    #[serde(default)]
+    pub transit_sort: bool,
+    /// Hide transit stops with no upcoming departures, and lines with no
+    /// stops left (unless they have alerts)
+    #[serde(default)]
+    pub transit_hide_empty: bool,
+    /// Bike-share stations to show availability for. If omitted, the bike
+    /// mode is hidden
+    #[serde(default)]
+    pub bike_share: Option<BikeShareConfig>,
 }
 
 impl Config {
@@ -21,4 +64,256 @@ impl Config {
         let file = File::open(Self::PATH).unwrap();
         serde_json::from_reader(file).unwrap()
     }
+
+    /// All weather locations, starting with the primary one
+    pub fn locations(&self) -> &[WeatherLocation] {
+        &self.locations
+    }
+
+    /// Location of the primary weather location, for anything that isn't
+    /// per-location. `None` if it isn't configured
+    pub fn coordinates(&self) -> Option<Coordinates> {
+        self.locations[0].coordinates
+    }
+}
+
+/// The primary location is configured with top-level fields, then any others
+/// are listed in `weather_locations`
+fn deserialize_locations<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<WeatherLocation>, D::Error> {
+    #[derive(Deserialize)]
+    struct Fields {
+        /// Location for daylight and air quality data. Optional, since
+        /// configs from before it was added only have NWS fields
+        #[serde(default)]
+        coordinates: Option<Coordinates>,
+        /// Where to fetch the weather forecast from. Configs from before
+        /// there was a choice of provider have top-level `forecast_office`
+        /// and `forecast_gridpoint` fields instead, which still work
+        #[serde(flatten, deserialize_with = "deserialize_weather")]
+        weather: WeatherConfig,
+        /// Name of the primary location, shown when paging between locations
+        #[serde(default = "default_location_name")]
+        location_name: String,
+        /// Additional places to show the weather for. The weather mode pages
+        /// between the primary location and these
+        #[serde(default)]
+        weather_locations: Vec<WeatherLocation>,
+    }
+
+    let fields = Fields::deserialize(deserializer)?;
+    let primary = WeatherLocation {
+        name: fields.location_name,
+        coordinates: fields.coordinates,
+        weather: fields.weather,
+    };
+    let locations: Vec<WeatherLocation> = iter::once(primary)
+        .chain(fields.weather_locations)
+        .collect();
+    // Open-Meteo looks up forecasts by location, so it can't do without
+    if let Some(location) = locations.iter().find(|location| {
+        matches!(location.weather, WeatherConfig::OpenMeteo)
+            && location.coordinates.is_none()
+    }) {
+        return Err(D::Error::custom(format!(
+            "Location `{}` needs `coordinates` for Open-Meteo",
+            location.name
+        )));
+    }
+    Ok(locations)
+}
+
+/// Accept either `weather`, or the legacy NWS-only fields
+fn deserialize_weather<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<WeatherConfig, D::Error> {
+    #[derive(Deserialize)]
+    struct Fields {
+        #[serde(default)]
+        weather: Option<WeatherConfig>,
+        #[serde(default)]
+        forecast_office: Option<String>,
+        #[serde(default)]
+        forecast_gridpoint: Option<(u32, u32)>,
+    }
+
+    let fields = Fields::deserialize(deserializer)?;
+    match fields {
+        Fields {
+            weather: Some(weather),
+            ..
+        } => Ok(weather),
+        Fields {
+            forecast_office: Some(office),
+            forecast_gridpoint: Some(gridpoint),
+            ..
+        } => Ok(WeatherConfig::Nws { office, gridpoint }),
+        _ => Err(D::Error::missing_field("weather")),
+    }
+}
+
+fn default_location_name() -> String {
+    "Home".into()
+}
+
+fn default_precipitation_threshold() -> f64 {
+    50.0
+}
+
+/// A named place to fetch the weather for
+#[derive(Clone, Debug, Deserialize)]
+pub struct WeatherLocation {
+    pub name: String,
+    /// Used for daylight data, and by providers that look up forecasts by
+    /// location. Without it, the weather mode leaves out anything that
+    /// depends on where the sun is
+    #[serde(default)]
+    pub coordinates: Option<Coordinates>,
+    /// Where to fetch the weather forecast from
+    pub weather: WeatherConfig,
+}
+
+/// Which glyphs to use for weather condition icons
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IconSet {
+    /// Symbols like ☀ and ☂. Requires a font with decent Unicode coverage
+    #[default]
+    Unicode,
+    /// Plain ASCII, for fonts that lack the symbols
+    Ascii,
+}
+
+/// How to show precipitation probability on the weather chart
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrecipitationDisplay {
+    /// A second line on the temperature chart, scaled to the temperature axis
+    #[default]
+    Line,
+    /// Bars below the chart with their own 0-100% scale, plus a row for
+    /// amounts when the weather provider has them
+    Bars,
+}
+
+/// A point on the globe, in degrees
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config(weather: serde_json::Value) -> Result<Config, serde_json::Error> {
+        let mut config = json!({"transit_lines": []});
+        config
+            .as_object_mut()
+            .unwrap()
+            .extend(weather.as_object().unwrap().clone());
+        serde_json::from_value(config)
+    }
+
+    #[test]
+    fn test_weather_provider() {
+        let config = config(json!({
+            "coordinates": {"latitude": 42.36, "longitude": -71.06},
+            "weather": {"provider": "open_meteo"},
+        }));
+        let config = config.unwrap();
+        assert!(matches!(
+            config.locations()[0].weather,
+            WeatherConfig::OpenMeteo
+        ));
+    }
+
+    /// Configs from before there was a choice of provider are NWS
+    #[test]
+    fn test_weather_legacy() {
+        let config = config(json!({
+            "forecast_office": "BOX",
+            "forecast_gridpoint": [71, 90],
+        }));
+        let config = config.unwrap();
+        let WeatherConfig::Nws { office, gridpoint } =
+            &config.locations()[0].weather
+        else {
+            panic!("Expected NWS config");
+        };
+        assert_eq!(office, "BOX");
+        assert_eq!(*gridpoint, (71, 90));
+        // They didn't have coordinates either
+        assert!(config.coordinates().is_none());
+    }
+
+    /// The primary location comes first, then any extras
+    #[test]
+    fn test_locations() {
+        let config = config(json!({
+            "coordinates": {"latitude": 42.36, "longitude": -71.06},
+            "location_name": "Boston",
+            "weather": {"provider": "open_meteo"},
+            "weather_locations": [{
+                "name": "Denver",
+                "coordinates": {"latitude": 39.74, "longitude": -104.99},
+                "weather": {"provider": "open_meteo"},
+            }],
+        }))
+        .unwrap();
+        let names: Vec<&str> = config
+            .locations()
+            .iter()
+            .map(|location| location.name.as_str())
+            .collect();
+        assert_eq!(names, ["Boston", "Denver"]);
+        assert_eq!(config.coordinates().unwrap().latitude, 42.36);
+    }
+
+    #[test]
+    fn test_transit_provider() {
+        let weather =
+            json!({"provider": "nws", "office": "BOX", "gridpoint": [71, 90]});
+        let default = config(json!({"weather": weather})).unwrap();
+        assert!(matches!(
+            default.transit,
+            TransitConfig::Mbta {
+                schedule_path: None
+            }
+        ));
+        let mbta = config(json!({
+            "weather": weather,
+            "transit": {"provider": "mbta", "schedule_path": "mbta.zip"},
+        }))
+        .unwrap();
+        let TransitConfig::Mbta { schedule_path } = &mbta.transit else {
+            panic!("Expected MBTA config");
+        };
+        assert_eq!(schedule_path.as_deref(), Some("mbta.zip"));
+    }
+
+    #[test]
+    fn test_weather_missing() {
+        assert!(config(json!({"forecast_office": "BOX"})).is_err());
+    }
+
+    #[test]
+    fn test_open_meteo_coordinates() {
+        assert!(
+            config(json!({"weather": {"provider": "open_meteo"}})).is_err()
+        );
+        // Every location needs them, not just the primary one
+        let config = config(json!({
+            "coordinates": {"latitude": 42.36, "longitude": -71.06},
+            "weather": {"provider": "open_meteo"},
+            "weather_locations": [{
+                "name": "Denver",
+                "weather": {"provider": "open_meteo"},
+            }],
+        }));
+        assert!(config.is_err());
+    }
 }
@@ -0,0 +1,348 @@
+//! Rolling log of hourly temperatures, so we can compare against the past.
+//! Stored as CSV next to the log file, so it survives restarts
+
+use crate::{units::TemperatureUnit, weather::WeatherForecast};
+use chrono::{DateTime, TimeDelta, Utc};
+use itertools::Itertools;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+};
+use tracing::{error, info};
+
+/// How long to keep records. A little over a week, so there's always a full
+/// week to chart
+const RETENTION: TimeDelta = TimeDelta::days(8);
+
+/// Hourly temperatures for every weather location
+#[derive(Debug)]
+pub struct History {
+    path: PathBuf,
+    records: Vec<Record>,
+}
+
+/// Temperature at one location for one hour
+#[derive(Clone, Debug)]
+struct Record {
+    time: DateTime<Utc>,
+    /// Always stored in °F, so changing units doesn't scramble the history
+    temperature: f64,
+    location: String,
+}
+
+impl History {
+    const PATH: &'static str = "./heisenberg-history.csv";
+
+    /// Load history from disk. A missing file just means we're starting
+    /// fresh. Any malformed lines are skipped. Records are only ever appended
+    /// while running, so this is where expired ones get cleaned out of the
+    /// file
+    pub fn load() -> Self {
+        Self::load_from(Self::PATH.into())
+    }
+
+    fn load_from(path: PathBuf) -> Self {
+        info!("Loading temperature history from `{}`", path.display());
+        let Ok(contents) = fs::read_to_string(&path) else {
+            return Self {
+                path,
+                records: Vec::new(),
+            };
+        };
+        let cutoff = Utc::now() - RETENTION;
+        let records: Vec<Record> = contents
+            .lines()
+            .filter_map(|line| {
+                let record = Record::parse(line);
+                if record.is_none() {
+                    error!("Invalid history line: `{line}`");
+                }
+                record
+            })
+            .filter(|record| record.time >= cutoff)
+            .collect();
+        let history = Self { path, records };
+        if history.records.len() < contents.lines().count() {
+            history.save();
+        }
+        history
+    }
+
+    /// Record the current temperature from a new forecast, and append it to
+    /// the file. There's one record per location per hour, from the first
+    /// forecast that covers it, so we only touch the disk once an hour
+    pub fn record(&mut self, location: &str, forecast: &WeatherForecast) {
+        let Some(current) = forecast.periods().next() else {
+            return;
+        };
+        let record = Record {
+            time: current.start_time().to_utc(),
+            temperature: forecast
+                .temperature_unit()
+                .to_fahrenheit(current.temp()),
+            location: location.to_owned(),
+        };
+        if self.records.iter().any(|existing| {
+            existing.time == record.time && existing.location == record.location
+        }) {
+            return;
+        }
+        let cutoff = Utc::now() - RETENTION;
+        self.records.retain(|existing| existing.time >= cutoff);
+        self.append(&record);
+        self.records.push(record);
+        self.records.sort_by_key(|record| record.time);
+    }
+
+    /// Temperature at a location within an hour of the given time, if we
+    /// have one
+    pub fn temperature_at(
+        &self,
+        location: &str,
+        time: DateTime<Utc>,
+        unit: TemperatureUnit,
+    ) -> Option<f64> {
+        self.records
+            .iter()
+            .filter(|record| record.location == location)
+            .map(|record| (record, (record.time - time).abs()))
+            .filter(|(_, distance)| *distance <= TimeDelta::hours(1))
+            .min_by_key(|(_, distance)| *distance)
+            .map(|(record, _)| unit.convert_from_fahrenheit(record.temperature))
+    }
+
+    /// All temperatures at a location since a point in time, oldest first
+    pub fn temperatures(
+        &self,
+        location: &str,
+        since: DateTime<Utc>,
+        unit: TemperatureUnit,
+    ) -> Vec<(DateTime<Utc>, f64)> {
+        self.records
+            .iter()
+            .filter(|record| {
+                record.location == location && record.time >= since
+            })
+            .map(|record| {
+                (
+                    record.time,
+                    unit.convert_from_fahrenheit(record.temperature),
+                )
+            })
+            .collect()
+    }
+
+    /// Write all records to disk, replacing whatever's there. Errors are
+    /// logged and otherwise ignored; losing history isn't worth crashing over
+    fn save(&self) {
+        let contents =
+            self.records.iter().map(|record| record.to_line()).join("");
+        if let Err(error) = fs::write(&self.path, contents) {
+            error!(%error, "Error saving history to `{}`", self.path.display());
+        }
+    }
+
+    /// Add one record to the end of the file
+    fn append(&self, record: &Record) {
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(record.to_line().as_bytes()));
+        if let Err(error) = result {
+            error!(%error, "Error saving history to `{}`", self.path.display());
+        }
+    }
+}
+
+impl Record {
+    /// Format as a line of the history file
+    fn to_line(&self) -> String {
+        format!(
+            "{},{:.1},{}\n",
+            self.time.timestamp(),
+            self.temperature,
+            self.location
+        )
+    }
+
+    /// Parse a `timestamp,temperature,location` line. Location is last so it
+    /// can contain commas
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(3, ',');
+        let time = DateTime::from_timestamp(fields.next()?.parse().ok()?, 0)?;
+        let temperature = fields.next()?.parse().ok()?;
+        let location = fields.next()?.to_owned();
+        Some(Self {
+            time,
+            temperature,
+            location,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::tests::current;
+    use std::path::Path;
+
+    /// A fresh file in the temp dir, unique to the test
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "heisenberg-history-{}-{name}.csv",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    /// Start of the current hour, since the file only stores whole seconds
+    fn this_hour() -> DateTime<Utc> {
+        let now = Utc::now().timestamp();
+        DateTime::from_timestamp(now - now % 3600, 0).unwrap()
+    }
+
+    fn lines(path: &Path) -> Vec<String> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let record = Record {
+            time: DateTime::from_timestamp(1748779200, 0).unwrap(),
+            temperature: 72.26,
+            location: "Boston, MA".into(),
+        };
+        let line = record.to_line();
+        assert_eq!(line, "1748779200,72.3,Boston, MA\n");
+        let parsed = Record::parse(line.trim_end()).unwrap();
+        assert_eq!(parsed.time, record.time);
+        assert_eq!(parsed.temperature, 72.3);
+        assert_eq!(parsed.location, "Boston, MA");
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for line in [
+            "",
+            "1748779200",
+            "1748779200,72.2",
+            "yesterday,72.2,Home",
+            "1748779200,warm,Home",
+        ] {
+            assert!(Record::parse(line).is_none(), "{line:?}");
+        }
+    }
+
+    /// Expired and malformed lines are dropped, and the file is rewritten
+    #[test]
+    fn test_load() {
+        let path = temp_path("load");
+        let line = |days: i64, temperature: f64| {
+            Record {
+                time: this_hour() - TimeDelta::days(days),
+                temperature,
+                location: "Home".into(),
+            }
+            .to_line()
+        };
+        let contents = [
+            line(9, 50.0),
+            line(7, 60.0),
+            "garbage\n".into(),
+            line(0, 70.0),
+        ]
+        .concat();
+        fs::write(&path, contents).unwrap();
+
+        let history = History::load_from(path.clone());
+        let temperatures: Vec<f64> = history
+            .temperatures(
+                "Home",
+                this_hour() - RETENTION,
+                TemperatureUnit::Fahrenheit,
+            )
+            .into_iter()
+            .map(|(_, temperature)| temperature)
+            .collect();
+        assert_eq!(temperatures, vec![60.0, 70.0]);
+        assert_eq!(
+            lines(&path),
+            vec![line(7, 60.0).trim_end(), line(0, 70.0).trim_end()]
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_load_missing() {
+        let path = temp_path("missing");
+        let history = History::load_from(path.clone());
+        assert!(history.records.is_empty());
+        assert!(!path.exists());
+    }
+
+    /// Only the first forecast each hour is recorded
+    #[test]
+    fn test_record() {
+        let path = temp_path("record");
+        let mut history = History::load_from(path.clone());
+        let now = this_hour();
+        let fahrenheit = TemperatureUnit::Fahrenheit;
+        history.record("Home", &current(now, 70.0, fahrenheit));
+        history.record("Home", &current(now, 75.0, fahrenheit));
+        history.record("Work", &current(now, 65.0, fahrenheit));
+        // Converted to °F for storage
+        let next = now + TimeDelta::hours(1);
+        history.record("Home", &current(next, 20.0, TemperatureUnit::Celsius));
+        assert_eq!(
+            lines(&path),
+            vec![
+                format!("{},70.0,Home", now.timestamp()),
+                format!("{},65.0,Work", now.timestamp()),
+                format!("{},68.0,Home", next.timestamp()),
+            ]
+        );
+        assert_eq!(history.temperatures("Home", now, fahrenheit).len(), 2);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_temperature_at() {
+        let now = this_hour();
+        let record = |hours: i64, temperature: f64| Record {
+            time: now + TimeDelta::hours(hours),
+            temperature,
+            location: "Home".into(),
+        };
+        let history = History {
+            path: PathBuf::new(),
+            records: vec![
+                record(-24, 50.0),
+                record(-23, 52.0),
+                record(0, 68.0),
+            ],
+        };
+        let at = |location: &str, minutes: i64, unit| {
+            history.temperature_at(
+                location,
+                now + TimeDelta::minutes(minutes),
+                unit,
+            )
+        };
+        let fahrenheit = TemperatureUnit::Fahrenheit;
+        assert_eq!(at("Home", -24 * 60, fahrenheit), Some(50.0));
+        // Nearest record wins
+        assert_eq!(at("Home", -23 * 60 - 20, fahrenheit), Some(52.0));
+        assert_eq!(at("Home", -22 * 60, fahrenheit), Some(52.0));
+        assert_eq!(at("Home", 0, TemperatureUnit::Celsius), Some(20.0));
+        // Nothing within an hour
+        assert_eq!(at("Home", -12 * 60, fahrenheit), None);
+        assert_eq!(at("Work", 0, fahrenheit), None);
+    }
+}
@@ -8,9 +8,16 @@
 //! This is a panic-first type program. Most errors are fatal. Anyhow has no
 //! power here!!
 
+mod air_quality;
+mod bike_share;
 mod config;
+mod history;
+mod moon;
+mod notify;
 mod state;
+mod sun;
 mod transit;
+mod units;
 mod util;
 mod view;
 mod weather;
@@ -37,6 +44,8 @@ use std::{
     fs::OpenOptions,
     io::{self, Stdout},
     sync::mpsc,
+    thread,
+    time::Duration,
 };
 use tracing::{info, level_filters::LevelFilter};
 use tracing_subscriber::{
@@ -44,6 +53,9 @@ use tracing_subscriber::{
     util::SubscriberInitExt,
 };
 
+/// Time between [Message::Tick]s
+const TICK_INTERVAL: Duration = Duration::from_secs(5);
+
 /// Initialize the TUI and start the main loop
 fn main() {
     initialize_tracing();
@@ -56,7 +68,7 @@ fn main() {
 
 /// Start the main program loop
 fn run(config: Config, mut terminal: DefaultTerminal) {
-    let mut state = State::default();
+    let mut state = State::new(config.clone());
 
     let (tx, rx) = mpsc::channel();
     let tx = Tx::new(tx);
@@ -69,10 +81,11 @@ fn run(config: Config, mut terminal: DefaultTerminal) {
     });
     spawn(&config, &tx, move |_, tx| {
         // Input handler
+        let mut press = None;
         loop {
             match event::read() {
                 Ok(event) => {
-                    if let Some(message) = input_message(event) {
+                    if let Some(message) = input_message(event, &mut press) {
                         tx.send(message);
                     }
                 }
@@ -81,17 +94,36 @@ fn run(config: Config, mut terminal: DefaultTerminal) {
             }
         }
     });
+    spawn(&config, &tx, move |_, tx| {
+        // Ticker, for anything that changes without new data
+        loop {
+            thread::sleep(TICK_INTERVAL);
+            tx.send(Message::Tick);
+        }
+    });
     spawn(&config, &tx, transit::transit_loop);
-    spawn(&config, &tx, weather::weather_loop);
+    for index in 0..config.locations().len() {
+        spawn(&config, &tx, move |config, tx| {
+            weather::weather_loop(config, tx, index)
+        });
+    }
+    spawn(&config, &tx, air_quality::air_quality_loop);
+    spawn(&config, &tx, bike_share::bike_share_loop);
 
     loop {
         terminal.draw(|frame| view::draw(frame, &state)).unwrap();
         // Block until we get a message
         match rx.recv().unwrap() {
-            Message::NextMode => state.mode = state.mode.next(),
+            Message::Tap { row } => state.tap(row),
+            Message::PageLocation(offset) => state.page_location(offset),
             Message::Quit => break,
-            Message::Transit(transit) => state.transit = transit,
-            Message::Weather(weather) => state.weather = weather,
+            Message::Tick => state.ticks += 1,
+            Message::Transit(transit) => state.update_transit(transit),
+            Message::Weather { index, weather } => {
+                state.update_weather(index, weather)
+            }
+            Message::AirQuality(air_quality) => state.air_quality = air_quality,
+            Message::BikeShare(bike_share) => state.bike_share = bike_share,
         }
     }
 }
@@ -155,17 +187,47 @@ fn restore_terminal() {
 }
 
 /// Handle user input and build the corresponding message. Return `None` if
-/// the event should be ignored
-fn input_message(event: Event) -> Option<Message> {
+/// the event should be ignored. `press` tracks where the current touch
+/// started, to tell taps apart from swipes
+fn input_message(
+    event: Event,
+    press: &mut Option<(u16, u16)>,
+) -> Option<Message> {
+    /// Minimum horizontal distance for a touch to count as a swipe, in columns
+    const SWIPE_DISTANCE: u16 = 4;
+
     match event {
         Event::Key(KeyEvent {
             code: KeyCode::Esc, ..
         }) => Some(Message::Quit),
-        // Cycle mode on click/tap
+        Event::Mouse(MouseEvent {
+            kind: MouseEventKind::Down(MouseButton::Left),
+            column,
+            row,
+            ..
+        }) => {
+            *press = Some((column, row));
+            None
+        }
         Event::Mouse(MouseEvent {
             kind: MouseEventKind::Up(MouseButton::Left),
+            column,
+            row,
             ..
-        }) => Some(Message::NextMode),
+        }) => {
+            let (start_column, start_row) =
+                press.take().unwrap_or((column, row));
+            if column.abs_diff(start_column) >= SWIPE_DISTANCE {
+                // Swipe left to go forward, like turning a page
+                Some(Message::PageLocation(if column < start_column {
+                    1
+                } else {
+                    -1
+                }))
+            } else {
+                Some(Message::Tap { row: start_row })
+            }
+        }
         _ => None,
     }
 }
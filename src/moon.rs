@@ -0,0 +1,202 @@
+//! Lunar phase calculations, computed locally from the date. Formulas are from
+//! Jean Meeus, *Astronomical Algorithms* (2nd ed.), chapters 48 and 49. Only
+//! the largest periodic terms are included, which is accurate to within a few
+//! minutes. We only display dates, so that's plenty.
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+/// Mean length of a lunar cycle, in days
+const SYNODIC_MONTH: f64 = 29.530588861;
+/// Julian day of the Unix epoch
+const UNIX_EPOCH_JD: f64 = 2440587.5;
+/// Julian day of the J2000.0 epoch
+const J2000_JD: f64 = 2451545.0;
+/// Julian day of the first new moon of 2000, which is lunation 0 for Meeus
+const LUNATION_0_JD: f64 = 2451550.09766;
+
+/// Current state of the moon
+#[derive(Copy, Clone, Debug)]
+pub struct MoonPhase {
+    pub phase: Phase,
+    /// Fraction of the disc that's lit, 0-1
+    pub illumination: f64,
+    pub next_new_moon: DateTime<Utc>,
+    pub next_full_moon: DateTime<Utc>,
+}
+
+impl MoonPhase {
+    /// Calculate the moon's phase at a particular time
+    pub fn at(time: DateTime<Utc>) -> Self {
+        let jd = julian_day(time);
+        let t = (jd - J2000_JD) / 36525.0;
+
+        // Meeus 47.2-47.4: Mean elongation of the moon, sun's mean anomaly,
+        // moon's mean anomaly
+        let d = (297.8501921 + 445267.1114034 * t).rem_euclid(360.0);
+        let m = (357.5291092 + 35999.0502909 * t).to_radians();
+        let m_prime = (134.9633964 + 477198.8675055 * t).to_radians();
+        let d_rad = d.to_radians();
+
+        // Meeus 48.4: Phase angle, and the illuminated fraction from that
+        let phase_angle = 180.0 - d - 6.289 * m_prime.sin() + 2.100 * m.sin()
+            - 1.274 * (2.0 * d_rad - m_prime).sin()
+            - 0.658 * (2.0 * d_rad).sin()
+            - 0.214 * (2.0 * m_prime).sin()
+            - 0.110 * d_rad.sin();
+        let illumination = (1.0 + phase_angle.to_radians().cos()) / 2.0;
+
+        // Elongation runs 0-360 through the cycle: 0 is new, 180 is full
+        let elongation = (180.0 - phase_angle).rem_euclid(360.0);
+        let phase = Phase::ALL[((elongation + 22.5) / 45.0) as usize % 8];
+
+        Self {
+            phase,
+            illumination,
+            next_new_moon: next_phase(jd, 0.0),
+            next_full_moon: next_phase(jd, 0.5),
+        }
+    }
+}
+
+/// Named phase of the moon, one for each eighth of the cycle
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Phase {
+    New,
+    WaxingCrescent,
+    FirstQuarter,
+    WaxingGibbous,
+    Full,
+    WaningGibbous,
+    LastQuarter,
+    WaningCrescent,
+}
+
+impl Phase {
+    /// All phases, in order through the cycle
+    const ALL: [Self; 8] = [
+        Self::New,
+        Self::WaxingCrescent,
+        Self::FirstQuarter,
+        Self::WaxingGibbous,
+        Self::Full,
+        Self::WaningGibbous,
+        Self::LastQuarter,
+        Self::WaningCrescent,
+    ];
+
+    /// Abbreviated name, short enough to share a line with other info
+    pub fn short_name(self) -> &'static str {
+        match self {
+            Self::New => "New",
+            Self::WaxingCrescent => "Wax Cres",
+            Self::FirstQuarter => "1st Qtr",
+            Self::WaxingGibbous => "Wax Gib",
+            Self::Full => "Full",
+            Self::WaningGibbous => "Wan Gib",
+            Self::LastQuarter => "3rd Qtr",
+            Self::WaningCrescent => "Wan Cres",
+        }
+    }
+}
+
+/// Find the first new (`fraction = 0.0`) or full (`fraction = 0.5`) moon
+/// after a Julian day
+fn next_phase(jd: f64, fraction: f64) -> DateTime<Utc> {
+    // Start from the lunation before the estimated current one, to make sure
+    // we don't skip over a phase due to the error in the mean cycle length
+    let mut k = ((jd - LUNATION_0_JD) / SYNODIC_MONTH).floor() - 1.0;
+    loop {
+        let phase_jd = phase_julian_day(k + fraction, fraction > 0.0);
+        if phase_jd > jd {
+            return from_julian_day(phase_jd);
+        }
+        k += 1.0;
+    }
+}
+
+/// Meeus chapter 49: Julian day of the new or full moon for a lunation number.
+/// `k` is an integer for new moons and ends in .5 for full moons
+fn phase_julian_day(k: f64, full: bool) -> f64 {
+    let t = k / 1236.85;
+    let mean = LUNATION_0_JD + SYNODIC_MONTH * k + 0.00015437 * t * t;
+
+    // Eccentricity of Earth's orbit
+    let e = 1.0 - 0.002516 * t;
+    let m = (2.5534 + 29.10535670 * k).to_radians();
+    let m_prime = (201.5643 + 385.81693528 * k).to_radians();
+    let f = (160.7108 + 390.67050284 * k).to_radians();
+    let omega = (124.7746 - 1.56375588 * k).to_radians();
+
+    // The five largest terms differ slightly between new and full moons
+    let (c1, c2, c3, c4, c5) = if full {
+        (-0.40614, 0.17302, 0.01614, 0.01043, 0.00734)
+    } else {
+        (-0.40720, 0.17241, 0.01608, 0.01039, 0.00739)
+    };
+    let correction = c1 * m_prime.sin()
+        + c2 * e * m.sin()
+        + c3 * (2.0 * m_prime).sin()
+        + c4 * (2.0 * f).sin()
+        + c5 * e * (m_prime - m).sin()
+        - 0.00514 * e * (m_prime + m).sin()
+        + 0.00208 * e * e * (2.0 * m).sin()
+        - 0.00111 * (m_prime - 2.0 * f).sin()
+        - 0.00057 * (m_prime + 2.0 * f).sin()
+        + 0.00056 * e * (2.0 * m_prime + m).sin()
+        - 0.00042 * (3.0 * m_prime).sin()
+        + 0.00042 * e * (m + 2.0 * f).sin()
+        + 0.00038 * e * (m - 2.0 * f).sin()
+        - 0.00024 * e * (2.0 * m_prime - m).sin()
+        - 0.00017 * omega.sin();
+    mean + correction
+}
+
+fn julian_day(time: DateTime<Utc>) -> f64 {
+    time.timestamp() as f64 / 86400.0 + UNIX_EPOCH_JD
+}
+
+fn from_julian_day(jd: f64) -> DateTime<Utc> {
+    let seconds = ((jd - UNIX_EPOCH_JD) * 86400.0) as i64;
+    DateTime::UNIX_EPOCH + TimeDelta::seconds(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    /// Assert two times are within a few minutes
+    fn assert_close(actual: DateTime<Utc>, expected: &str) {
+        let difference = (actual - time(expected)).abs();
+        assert!(
+            difference <= TimeDelta::minutes(5),
+            "Expected {expected}, got {actual}"
+        );
+    }
+
+    /// The new moon of the April 2024 total eclipse, and the full moon after
+    #[test]
+    fn test_next_phases() {
+        let moon = MoonPhase::at(time("2024-04-01T00:00:00Z"));
+        assert_eq!(moon.phase, Phase::LastQuarter);
+        assert_close(moon.next_new_moon, "2024-04-08T18:21:00Z");
+        assert_close(moon.next_full_moon, "2024-04-23T23:49:00Z");
+    }
+
+    #[test]
+    fn test_phase() {
+        let new = MoonPhase::at(time("2024-04-08T18:21:00Z"));
+        assert_eq!(new.phase, Phase::New);
+        assert!(new.illumination < 0.01);
+        // Just after, the next new moon is a whole cycle away
+        let after = MoonPhase::at(time("2024-04-08T19:00:00Z"));
+        assert_close(after.next_new_moon, "2024-05-08T03:22:00Z");
+
+        let full = MoonPhase::at(time("2024-04-23T23:49:00Z"));
+        assert_eq!(full.phase, Phase::Full);
+        assert!(full.illumination > 0.99);
+    }
+}
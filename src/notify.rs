@@ -0,0 +1,61 @@
+//! Outbound notifications, for things worth knowing about even when nobody is
+//! looking at the screen
+
+use serde::Deserialize;
+use serde_json::json;
+use std::{process::Command, time::Duration};
+use tracing::{error, info};
+
+/// Where to send a notification
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotifierConfig {
+    /// POST a JSON body of `{"message": "..."}` to a URL
+    Webhook { url: String },
+    /// Run a program, with the message appended as the final argument
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+/// Give up on webhooks that take longer than this
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Something that can deliver a notification message
+pub trait Notifier {
+    /// Send a message. Errors are logged by the notifier
+    fn notify(&self, message: &str);
+}
+
+impl Notifier for NotifierConfig {
+    fn notify(&self, message: &str) {
+        match self {
+            Self::Webhook { url } => {
+                info!("Sending notification to {url}");
+                let agent = ureq::Agent::config_builder()
+                    .timeout_global(Some(WEBHOOK_TIMEOUT))
+                    .build()
+                    .new_agent();
+                if let Err(error) =
+                    agent.post(url).send_json(json!({ "message": message }))
+                {
+                    error!(%error, "Error sending notification to {url}");
+                }
+            }
+            Self::Command { program, args } => {
+                info!("Running notification command `{program}`");
+                match Command::new(program).args(args).arg(message).status() {
+                    Ok(status) if status.success() => {}
+                    Ok(status) => {
+                        error!(%status, "Notification command `{program}` failed")
+                    }
+                    Err(error) => {
+                        error!(%error, "Error running `{program}`")
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,37 +1,148 @@
-use crate::{transit::TransitPredictions, weather::WeatherForecast};
+use crate::{
+    air_quality::AirQuality, bike_share::BikeShare, config::Config,
+    history::History, transit::TransitPredictions, view,
+    weather::WeatherForecast,
+};
 use std::{
     fmt::{self, Display},
     sync::mpsc::Sender,
 };
 
+/// Row of the weather location header, below the mode tabs. Tapping it pages
+/// to the next location
+pub const LOCATION_ROW: u16 = 1;
+
 /// Global application state. This is modified by [Message]s sent to an
 /// mpsc channel
 pub struct State {
+    pub config: Config,
     pub transit: TransitPredictions,
-    pub weather: WeatherForecast,
+    /// Forecast for each of [Config::locations], in order
+    pub weather: Vec<WeatherForecast>,
+    /// Index of the weather location being displayed
+    pub location: usize,
+    /// Past temperatures for all locations
+    pub history: History,
+    /// Index of the transit line whose alerts are expanded, if any
+    pub expanded_alert: Option<usize>,
+    pub air_quality: AirQuality,
+    pub bike_share: BikeShare,
     pub mode: Mode,
+    /// Number of [Message::Tick]s received, for anything that animates
+    pub ticks: usize,
 }
 
-impl Default for State {
-    fn default() -> Self {
+impl State {
+    pub fn new(config: Config) -> Self {
+        let locations = config.locations().len();
         Self {
+            config,
             mode: Mode::Weather,
             transit: TransitPredictions::default(),
-            weather: WeatherForecast::default(),
+            weather: vec![WeatherForecast::default(); locations],
+            location: 0,
+            history: History::load(),
+            expanded_alert: None,
+            air_quality: AirQuality::default(),
+            bike_share: BikeShare::default(),
+            ticks: 0,
+        }
+    }
+
+    /// Forecast for the weather location being displayed
+    pub fn weather(&self) -> &WeatherForecast {
+        &self.weather[self.location]
+    }
+
+    /// Name of the weather location being displayed
+    pub fn location_name(&self) -> &str {
+        &self.config.locations()[self.location].name
+    }
+
+    /// Store a new forecast for a location, and log its current temperature
+    pub fn update_weather(&mut self, index: usize, weather: WeatherForecast) {
+        let name = &self.config.locations()[index].name;
+        self.history.record(name, &weather);
+        self.weather[index] = weather;
+    }
+
+    /// Store new transit predictions, sorted and filtered per the config.
+    /// If a line's alerts are expanded, keep them expanded even if the line
+    /// moved
+    pub fn update_transit(&mut self, mut transit: TransitPredictions) {
+        if self.config.transit_hide_empty {
+            transit.hide_empty();
+        }
+        if self.config.transit_sort {
+            transit.sort_by_departure();
+        }
+        self.expanded_alert = self.expanded_alert.and_then(|index| {
+            let name = &self.transit.lines.get(index)?.name;
+            transit.lines.iter().position(|line| line.name == *name)
+        });
+        self.transit = transit;
+    }
+
+    /// Handle a tap on the screen. Tapping the location header pages to the
+    /// next location, and tapping a transit line with alerts expands them.
+    /// Anywhere else switches to the next mode
+    pub fn tap(&mut self, row: u16) {
+        if self.mode == Mode::Transit {
+            // Any tap closes the alert detail
+            if self.expanded_alert.take().is_some() {
+                return;
+            }
+            if let Some(index) = view::transit_line_at(self, row)
+                && !self.transit.lines[index].alerts.is_empty()
+            {
+                self.expanded_alert = Some(index);
+                return;
+            }
+        }
+
+        if row == LOCATION_ROW
+            && self.mode.has_locations()
+            && self.weather.len() > 1
+        {
+            self.page_location(1);
+        } else {
+            self.mode = self.mode.next(&self.config);
+        }
+    }
+
+    /// Move forward or backward through the weather locations, wrapping
+    /// around at either end. Only applies to modes that show a location
+    pub fn page_location(&mut self, offset: isize) {
+        if self.mode.has_locations() {
+            self.location = (self.location as isize + offset)
+                .rem_euclid(self.weather.len() as isize)
+                as usize;
         }
     }
 }
 
 /// A message is sent from background threads to the main thread to modify state
 pub enum Message {
-    /// Switch to the next tab in the list
-    NextMode,
+    /// Screen was tapped on the given row
+    Tap { row: u16 },
+    /// Screen was swiped horizontally. Move this many weather locations
+    PageLocation(isize),
     /// Exit the program
     Quit,
+    /// Sent on a fixed interval, to advance anything that animates
+    Tick,
     /// Update transit predictions
     Transit(TransitPredictions),
-    /// Update the weather forecast
-    Weather(WeatherForecast),
+    /// Update the weather forecast for one location
+    Weather {
+        /// Position of the location in [Config::locations]
+        index: usize,
+        weather: WeatherForecast,
+    },
+    /// Update air quality and pollen
+    AirQuality(AirQuality),
+    /// Update bike-share station availability
+    BikeShare(BikeShare),
 }
 
 /// Message sender channel
@@ -57,16 +168,53 @@ impl Tx {
 pub enum Mode {
     Weather,
     Transit,
+    AirQuality,
+    History,
+    BikeShare,
 }
 
 impl Mode {
     /// List of all modes
-    pub const ALL: [Self; 2] = [Self::Weather, Self::Transit];
+    pub const ALL: [Self; 5] = [
+        Self::Weather,
+        Self::Transit,
+        Self::AirQuality,
+        Self::History,
+        Self::BikeShare,
+    ];
+
+    /// Modes to show, in order. Bike share is left out unless it's
+    /// configured, since it would always be empty
+    pub fn enabled(config: &Config) -> Vec<Self> {
+        Self::ALL
+            .into_iter()
+            .filter(|mode| {
+                *mode != Self::BikeShare || config.bike_share.is_some()
+            })
+            .collect()
+    }
+
+    /// Get the next enabled mode
+    pub fn next(self, config: &Config) -> Self {
+        let modes = Self::enabled(config);
+        let current = modes.iter().position(|m| *m == self).unwrap_or(0);
+        modes[(current + 1) % modes.len()]
+    }
+
+    /// Abbreviated name, for when the full tab names don't fit
+    pub fn short_name(self) -> &'static str {
+        match self {
+            Self::Weather => "Wthr",
+            Self::Transit => "Trans",
+            Self::AirQuality => "Air",
+            Self::History => "Hist",
+            Self::BikeShare => "Bike",
+        }
+    }
 
-    /// Get the next mode in the list
-    pub fn next(self) -> Self {
-        let current = Self::ALL.iter().position(|m| *m == self).unwrap();
-        Self::ALL[(current + 1) % Self::ALL.len()]
+    /// Does this mode show data for a single weather location?
+    pub fn has_locations(self) -> bool {
+        matches!(self, Self::Weather | Self::History)
     }
 }
 
@@ -75,6 +223,10 @@ impl Display for Mode {
         match self {
             Self::Weather => write!(f, "Weather"),
             Self::Transit => write!(f, "Transit"),
+            Self::AirQuality => write!(f, "Air"),
+            // Abbreviated so all the tabs fit
+            Self::History => write!(f, "Hist"),
+            Self::BikeShare => write!(f, "Bike"),
         }
     }
 }
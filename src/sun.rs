@@ -0,0 +1,173 @@
+//! Solar position calculations, so we can tell day from night without asking
+//! an API. Uses the NOAA approximations, which are accurate to within a few
+//! minutes for non-polar latitudes.
+//!
+//! https://gml.noaa.gov/grad/solcalc/solareqns.PDF
+
+use crate::config::Coordinates;
+use chrono::{DateTime, Datelike, NaiveDate, TimeDelta, Timelike, Utc};
+use std::f64::consts::PI;
+
+/// Solar elevation at sunrise/sunset, in degrees. This accounts for
+/// atmospheric refraction and the size of the sun's disc
+const SUNRISE_ELEVATION: f64 = -0.833;
+/// Solar elevation at the start/end of civil twilight, in degrees
+const CIVIL_TWILIGHT_ELEVATION: f64 = -6.0;
+
+/// How much light is there?
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Daylight {
+    /// Sun is above the horizon
+    Day,
+    /// Sun is below the horizon, but not far enough to be dark
+    Twilight,
+    Night,
+}
+
+impl Daylight {
+    /// Determine whether it's day or night at a given time and place
+    pub fn at(time: DateTime<Utc>, coordinates: Coordinates) -> Self {
+        let elevation = solar_elevation(time, coordinates);
+        if elevation > SUNRISE_ELEVATION {
+            Self::Day
+        } else if elevation > CIVIL_TWILIGHT_ELEVATION {
+            Self::Twilight
+        } else {
+            Self::Night
+        }
+    }
+}
+
+/// Get the sunrise and sunset times for a date. The date is interpreted in
+/// the timezone of the coordinates, so the returned times may fall on an
+/// adjacent UTC date. Return `None` if the sun doesn't rise or doesn't set on
+/// this date (polar day/night)
+pub fn sunrise_sunset(
+    date: NaiveDate,
+    coordinates: Coordinates,
+) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    let midnight = date.and_hms_opt(0, 0, 0)?.and_utc();
+    let (declination, equation_of_time) = solar_parameters(midnight, 12.0);
+    let latitude = coordinates.latitude.to_radians();
+
+    // Hour angle of the sun at sunrise/sunset, in degrees
+    let cos_hour_angle = SUNRISE_ELEVATION.to_radians().sin()
+        / (latitude.cos() * declination.cos())
+        - latitude.tan() * declination.tan();
+    if !(-1.0..=1.0).contains(&cos_hour_angle) {
+        return None;
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+
+    // Minutes after midnight UTC. 4 minutes per degree of rotation
+    let solar_noon = 720.0 - 4.0 * coordinates.longitude - equation_of_time;
+    let minutes =
+        |minutes: f64| midnight + TimeDelta::seconds((minutes * 60.0) as i64);
+    Some((
+        minutes(solar_noon - 4.0 * hour_angle),
+        minutes(solar_noon + 4.0 * hour_angle),
+    ))
+}
+
+/// Angle of the sun above the horizon, in degrees
+fn solar_elevation(time: DateTime<Utc>, coordinates: Coordinates) -> f64 {
+    let hours = time.hour() as f64
+        + time.minute() as f64 / 60.0
+        + time.second() as f64 / 3600.0;
+    let (declination, equation_of_time) = solar_parameters(time, hours);
+    let latitude = coordinates.latitude.to_radians();
+
+    // Minutes since solar midnight
+    let true_solar_time =
+        hours * 60.0 + equation_of_time + 4.0 * coordinates.longitude;
+    let hour_angle = (true_solar_time / 4.0 - 180.0).to_radians();
+
+    let cos_zenith = latitude.sin() * declination.sin()
+        + latitude.cos() * declination.cos() * hour_angle.cos();
+    90.0 - cos_zenith.clamp(-1.0, 1.0).acos().to_degrees()
+}
+
+/// Get the solar declination (radians) and equation of time (minutes) for a
+/// particular hour of a day
+fn solar_parameters(date: DateTime<Utc>, hours: f64) -> (f64, f64) {
+    // Fractional year, in radians
+    let gamma =
+        2.0 * PI / 365.0 * (date.ordinal0() as f64 + (hours - 12.0) / 24.0);
+    let equation_of_time = 229.18
+        * (0.000075 + 0.001868 * gamma.cos()
+            - 0.032077 * gamma.sin()
+            - 0.014615 * (2.0 * gamma).cos()
+            - 0.040849 * (2.0 * gamma).sin());
+    let declination = 0.006918 - 0.399912 * gamma.cos()
+        + 0.070257 * gamma.sin()
+        - 0.006758 * (2.0 * gamma).cos()
+        + 0.000907 * (2.0 * gamma).sin()
+        - 0.002697 * (3.0 * gamma).cos()
+        + 0.00148 * (3.0 * gamma).sin();
+    (declination, equation_of_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOSTON: Coordinates = Coordinates {
+        latitude: 42.36,
+        longitude: -71.06,
+    };
+    const TROMSO: Coordinates = Coordinates {
+        latitude: 69.65,
+        longitude: 18.96,
+    };
+
+    fn time(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    /// Assert two times are within a few minutes, which is about as accurate
+    /// as the NOAA approximations get
+    fn assert_close(actual: DateTime<Utc>, expected: &str) {
+        let difference = (actual - time(expected)).abs();
+        assert!(
+            difference <= TimeDelta::minutes(2),
+            "Expected {expected}, got {actual}"
+        );
+    }
+
+    /// Checked against the NOAA solar calculator
+    #[test]
+    fn test_sunrise_sunset() {
+        let date = NaiveDate::from_ymd_opt(2025, 6, 21).unwrap();
+        let (sunrise, sunset) = sunrise_sunset(date, BOSTON).unwrap();
+        assert_close(sunrise, "2025-06-21T09:07:00Z");
+        assert_close(sunset, "2025-06-22T00:25:00Z");
+
+        let date = NaiveDate::from_ymd_opt(2025, 12, 21).unwrap();
+        let (sunrise, sunset) = sunrise_sunset(date, BOSTON).unwrap();
+        assert_close(sunrise, "2025-12-21T12:11:00Z");
+        assert_close(sunset, "2025-12-21T21:15:00Z");
+    }
+
+    #[test]
+    fn test_sunrise_sunset_polar() {
+        let summer = NaiveDate::from_ymd_opt(2025, 6, 21).unwrap();
+        assert_eq!(sunrise_sunset(summer, TROMSO), None);
+        let winter = NaiveDate::from_ymd_opt(2025, 12, 21).unwrap();
+        assert_eq!(sunrise_sunset(winter, TROMSO), None);
+    }
+
+    #[test]
+    fn test_daylight() {
+        let at = |t| Daylight::at(time(t), BOSTON);
+        // Noon and midnight EDT
+        assert_eq!(at("2025-06-21T16:00:00Z"), Daylight::Day);
+        assert_eq!(at("2025-06-21T04:00:00Z"), Daylight::Night);
+        // 15 minutes after sunset
+        assert_eq!(at("2025-06-22T00:40:00Z"), Daylight::Twilight);
+        // Midnight sun
+        assert_eq!(
+            Daylight::at(time("2025-06-21T23:00:00Z"), TROMSO),
+            Daylight::Day
+        );
+    }
+}
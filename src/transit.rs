@@ -1,54 +1,139 @@
+mod gtfs_realtime;
+mod gtfs_static;
+mod mbta;
+
 use crate::{
     config::Config,
     state::{Message, Tx},
-    util::http_get,
+    transit::{
+        gtfs_realtime::GtfsRealtime, gtfs_static::GtfsSchedule, mbta::Mbta,
+    },
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use itertools::Itertools;
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 use std::{
-    collections::HashMap,
     fmt::{self, Display},
     thread,
     time::Duration,
 };
-use tracing::error;
 
 /// Time between requests
 const DATA_TTL: Duration = Duration::from_secs(30);
+/// How many times to poll after the prediction stream drops, before trying to
+/// reconnect
+const POLLS_BEFORE_STREAM_RETRY: usize = 4;
 /// Max number of pending departures to show for a stop
 const MAX_PREDICTIONS: usize = 2;
 
-/// Fetch transit data in a loop. When we get new predictions, send a message to
-/// update state
+/// Fetch transit data in a loop. Predictions are streamed when the provider
+/// supports it, and polled otherwise. When we get new predictions, send a
+/// message to update state
 pub fn transit_loop(config: Config, tx: Tx) {
-    let stop_ids = config
-        .transit_lines
-        .iter()
-        .flat_map(|line| &line.stops)
-        .map(|stop| stop.id);
-    let url = format!(
-        "https://api-v3.mbta.com/predictions?filter[stop]={}",
-        stop_ids.format(",")
-    );
+    let mut provider: Box<dyn TransitProvider> = match &config.transit {
+        TransitConfig::Mbta { schedule_path } => {
+            let schedule =
+                load_fallback(schedule_path.as_deref(), &config.transit_lines);
+            Box::new(Mbta::new(&config.transit_lines, schedule))
+        }
+        TransitConfig::GtfsRealtime {
+            trip_updates_url,
+            schedule_path,
+        } => {
+            let schedule =
+                load_fallback(schedule_path.as_deref(), &config.transit_lines);
+            Box::new(GtfsRealtime::new(
+                trip_updates_url,
+                &config.transit_lines,
+                schedule,
+            ))
+        }
+        TransitConfig::GtfsSchedule { path } => {
+            match GtfsSchedule::load(path, &config.transit_lines) {
+                Ok(schedule) => Box::new(schedule),
+                // Error is already logged, and there's nothing to show
+                Err(()) => return,
+            }
+        }
+    };
 
+    let mut send = |predictions| tx.send(Message::Transit(predictions));
     loop {
-        if let Ok(api_data) = http_get::<ApiPredictions>(&url) {
-            let predictions = TransitPredictions::from_response(
-                &config.transit_lines,
-                api_data,
-            );
-            tx.send(Message::Transit(predictions));
+        provider.stream(&mut send);
+        // Stream dropped, or there isn't one. Poll for a bit, then give it
+        // another shot
+        for _ in 0..POLLS_BEFORE_STREAM_RETRY {
+            if let Ok(predictions) = provider.fetch() {
+                send(predictions);
+            }
+            thread::sleep(DATA_TTL);
+        }
+    }
+}
+
+/// Load the static schedule that a real-time provider falls back to, if
+/// there is one. Errors are logged, and we just go without
+fn load_fallback(
+    schedule_path: Option<&str>,
+    lines: &[TransitLine],
+) -> Option<GtfsSchedule> {
+    GtfsSchedule::load(schedule_path?, lines).ok()
+}
+
+/// Where to fetch transit predictions from
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum TransitConfig {
+    /// MBTA v3 API (Boston)
+    Mbta {
+        /// Path to the MBTA's GTFS static zip, to fall back to when the API
+        /// is down
+        #[serde(default)]
+        schedule_path: Option<String>,
+    },
+    /// Any agency that publishes a GTFS-Realtime TripUpdates feed
+    GtfsRealtime {
+        /// URL of the TripUpdates protobuf feed
+        trip_updates_url: String,
+        /// Path to a GTFS static zip, to fall back to when the feed is down
+        #[serde(default)]
+        schedule_path: Option<String>,
+    },
+    /// Scheduled times only, from a GTFS static zip. For agencies without
+    /// real-time data
+    GtfsSchedule {
+        /// Path to the GTFS zip
+        path: String,
+    },
+}
+
+impl Default for TransitConfig {
+    fn default() -> Self {
+        Self::Mbta {
+            schedule_path: None,
         }
-        thread::sleep(DATA_TTL);
     }
 }
 
+/// A source of real-time transit predictions
+pub trait TransitProvider {
+    /// Fetch the latest predictions. Errors are logged by the provider
+    fn fetch(&mut self) -> Result<TransitPredictions, ()>;
+
+    /// Push new predictions whenever they change, until the connection
+    /// drops. Providers that can't stream return immediately, and are polled
+    /// with [Self::fetch] instead
+    fn stream(&mut self, _on_update: &mut dyn FnMut(TransitPredictions)) {}
+}
+
 /// Configuration for a transit line to show predictions for
 #[derive(Clone, Debug, Deserialize)]
 pub struct TransitLine {
     /// Display name for the line
     pub name: String,
+    /// Provider's route ID, if it's different from the display name
+    #[serde(default)]
+    route_id: Option<String>,
     /// Stops on the line to track
     pub stops: Vec<Stop>,
 }
@@ -58,8 +143,38 @@ pub struct TransitLine {
 pub struct Stop {
     /// Display name for the stop
     pub name: String,
-    /// MBTA API stop ID
-    pub id: u32,
+    /// Provider's stop ID. Accepts numbers for backward compatibility
+    #[serde(deserialize_with = "deserialize_stop_id")]
+    pub id: String,
+    /// Minutes it takes to walk to the stop. Departures that leave sooner
+    /// than this are hidden
+    #[serde(default)]
+    pub walk_minutes: Option<u32>,
+}
+
+impl TransitLine {
+    /// ID the provider uses for this line's route
+    pub fn route_id(&self) -> &str {
+        self.route_id.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// Stop IDs are strings, but they used to be numbers and plenty of configs
+/// still have them that way
+fn deserialize_stop_id<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StopId {
+        Number(u64),
+        String(String),
+    }
+
+    Ok(match StopId::deserialize(deserializer)? {
+        StopId::Number(id) => id.to_string(),
+        StopId::String(id) => id,
+    })
 }
 
 /// Predictions for all tracked transit lines/stops
@@ -71,60 +186,61 @@ pub struct TransitPredictions {
 }
 
 impl TransitPredictions {
-    /// Gather predictions from
-    fn from_response(
-        lines: &[TransitLine],
-        api_data: ApiPredictions,
-    ) -> TransitPredictions {
-        // Group API data as {(line, stop): [prediction]}
-        let mut grouped: HashMap<(&str, u32), Vec<DateTime<Utc>>> = api_data
-            .data
-            .iter()
-            .filter_map(|prediction| {
-                // Departure time will be empty if the stop is being skipped
-                let departure_time = prediction.attributes.departure_time?;
-                let route_id = prediction.relationships.route.data.id.as_str();
-                let stop_id =
-                    prediction.relationships.stop.data.id.parse::<u32>().inspect_err(|error|
-                    error!(%error, "Invalid stop ID in API response")
-                    ).ok()?;
-
-                Some(((route_id, stop_id), departure_time))
-            })
-            .into_group_map();
-
-        let lines = lines
-            .iter()
-            .map(|line| {
-                let stops = line
-                    .stops
+    /// Order lines and their stops by soonest departure. Anything with no
+    /// departures goes last, in its original order
+    pub fn sort_by_departure(&mut self) {
+        for line in &mut self.lines {
+            line.stops.sort_by_key(|stop| sort_key(stop.next_minutes()));
+        }
+        self.lines.sort_by_key(|line| {
+            sort_key(
+                line.stops
                     .iter()
-                    .map(|stop| {
-                        let predictions: CountdownList = grouped
-                            .remove(&(&line.name, stop.id))
-                            .unwrap_or_default()
-                            .into();
-                        StopPredictions {
-                            name: stop.name.clone(),
-                            predictions,
-                        }
-                    })
-                    .collect();
-                LinePredictions {
-                    name: line.name.clone(),
-                    stops,
-                }
-            })
-            .collect();
-        TransitPredictions { lines }
+                    .filter_map(StopPredictions::next_minutes)
+                    .min(),
+            )
+        });
+    }
+
+    /// Remove stops with no departures, and then lines with no stops. Lines
+    /// with alerts are kept, since the alert is probably why it's empty
+    pub fn hide_empty(&mut self) {
+        for line in &mut self.lines {
+            line.stops.retain(|stop| !stop.predictions.is_empty());
+        }
+        self.lines
+            .retain(|line| !line.stops.is_empty() || !line.alerts.is_empty());
     }
 }
 
+/// Sort soonest first, with missing values at the end
+fn sort_key(minutes: Option<i64>) -> (bool, Option<i64>) {
+    (minutes.is_none(), minutes)
+}
+
 /// Arrival predictions for all stops on a line, ready to be displayed
 #[derive(Debug)]
 pub struct LinePredictions {
     pub name: String,
     pub stops: Vec<StopPredictions>,
+    /// Active service alerts affecting this line, most severe first
+    pub alerts: Vec<Alert>,
+}
+
+/// A service alert affecting a line or one of its tracked stops
+#[derive(Clone, Debug)]
+pub struct Alert {
+    /// Short description of the impact, e.g. "Suspension"
+    pub effect: String,
+    /// Full alert text
+    pub header: String,
+    /// 0-10, higher is worse
+    pub severity: u8,
+}
+
+impl Alert {
+    /// Severity at which an alert is considered major
+    pub const SEVERE: u8 = 7;
 }
 
 /// Arrival predictions for a single stop, ready to be displayed
@@ -132,77 +248,467 @@ pub struct LinePredictions {
 pub struct StopPredictions {
     pub name: String,
     pub predictions: CountdownList,
+    /// Minutes until you need to leave to make the next departure. Only set
+    /// if the stop has a walking time
+    pub leave_in: Option<i64>,
+}
+
+impl StopPredictions {
+    /// Build predictions for a stop, leaving out departures that can't be
+    /// reached in time
+    fn new(
+        stop: &Stop,
+        departures: Vec<Departure>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let walk = stop.walk_minutes.unwrap_or(0).into();
+        let predictions = CountdownList::new(departures, now, walk);
+        let leave_in = stop
+            .walk_minutes
+            .and_then(|_| Some(predictions.next()?.minutes - walk));
+        Self {
+            name: stop.name.clone(),
+            predictions,
+            leave_in,
+        }
+    }
+}
+
+impl StopPredictions {
+    /// Minutes until the next departure that's actually running
+    fn next_minutes(&self) -> Option<i64> {
+        Some(self.predictions.next()?.minutes)
+    }
+}
+
+/// A single departure from a stop, from either a prediction or a schedule
+#[derive(Copy, Clone, Debug)]
+struct Departure {
+    time: DateTime<Utc>,
+    /// When the vehicle gets to the stop, if known. Countdowns are to the
+    /// arrival, since that's when you need to be there
+    arrival: Option<DateTime<Utc>>,
+    /// Scheduled departure time, to compare against the prediction
+    scheduled: Option<DateTime<Utc>>,
+    /// Did this come from a real-time prediction?
+    realtime: bool,
+    /// Status given by the provider, which overrides the countdown
+    status: Option<Status>,
+    /// Live info on the vehicle serving this departure
+    vehicle: Option<Vehicle>,
+}
+
+/// Where the vehicle for a departure is, and how full it is
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Vehicle {
+    /// Number of stops until it gets to our stop
+    pub stops_away: Option<u32>,
+    pub crowding: Option<Crowding>,
+}
+
+/// How full a vehicle is. Same levels the MBTA uses in its apps
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Crowding {
+    NotCrowded,
+    SomewhatCrowded,
+    Crowded,
+}
+
+/// Shown in place of a minute count, following the MBTA's countdown display
+/// conventions
+///
+/// <https://www.mbta.com/developers/v3-api/best-practices>
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Status {
+    /// Vehicle is at the stop
+    Boarding,
+    /// Arriving within 30 seconds
+    Arriving,
+    /// Arriving within a minute
+    Approaching,
+    Delayed,
+    Cancelled,
 }
 
 /// List of upcoming arrivals for a stop
 #[derive(Debug)]
 pub struct CountdownList(Vec<Countdown>);
 
-/// Convert a list of timestamps into relative offsets from now, sorting and
-/// truncating as necessary
-impl From<Vec<DateTime<Utc>>> for CountdownList {
-    fn from(value: Vec<DateTime<Utc>>) -> Self {
-        let now = Utc::now();
-        let countdowns = value
+impl CountdownList {
+    pub fn iter(&self) -> impl Iterator<Item = &Countdown> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Next departure that's actually running
+    pub fn next(&self) -> Option<&Countdown> {
+        self.iter().find(|countdown| !countdown.is_cancelled())
+    }
+}
+
+impl CountdownList {
+    /// Convert a list of departures into relative offsets from `now`, sorting
+    /// and truncating as necessary. Departures that leave within
+    /// `walk_minutes` (i.e. already left, or can't be reached) are dropped
+    fn new(
+        departures: Vec<Departure>,
+        now: DateTime<Utc>,
+        walk_minutes: i64,
+    ) -> Self {
+        let earliest = now + TimeDelta::minutes(walk_minutes);
+        let countdowns = departures
             .into_iter()
-            // Get the first n upcoming timestamps
-            .sorted()
+            .filter(|departure| departure.time >= earliest)
+            // Get the first n upcoming departures
+            .sorted_by_key(|departure| departure.time)
             .take(MAX_PREDICTIONS)
-            .map(|dt| Countdown((dt - now).num_minutes()))
+            .map(|departure| Countdown::new(departure, now))
             .collect();
         Self(countdowns)
     }
 }
 
-impl Display for CountdownList {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if self.0.is_empty() {
-            write!(f, "None")
+/// Number of minutes until an event
+#[derive(Debug)]
+pub struct Countdown {
+    minutes: i64,
+    /// `false` if this is only a scheduled time, with no real-time tracking
+    realtime: bool,
+    /// If set, this is shown instead of the minutes
+    status: Option<Status>,
+    /// Minutes behind schedule. Negative is early. `None` if there's no
+    /// schedule to compare to
+    delay: Option<i64>,
+    vehicle: Option<Vehicle>,
+}
+
+/// How far behind schedule a departure is
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Lateness {
+    OnTime,
+    /// A few minutes late
+    Late,
+    /// Badly delayed
+    VeryLate,
+}
+
+impl Lateness {
+    /// Minutes late, beyond which a departure isn't on time anymore
+    const ON_TIME_MAX: i64 = 1;
+    /// Minutes late, beyond which a departure is badly delayed
+    const LATE_MAX: i64 = 5;
+}
+
+impl Countdown {
+    fn new(departure: Departure, now: DateTime<Utc>) -> Self {
+        let until = departure.arrival.unwrap_or(departure.time) - now;
+        let seconds = until.num_seconds();
+        // Scheduled times don't know where the vehicle is, so they just
+        // count down
+        let status = departure.status.or(if !departure.realtime {
+            None
+        } else if seconds <= 0 {
+            // Arrived but hasn't left yet
+            Some(Status::Boarding)
+        } else if seconds <= 30 {
+            Some(Status::Arriving)
+        } else if seconds <= 60 {
+            Some(Status::Approaching)
         } else {
-            write!(f, "{}", self.0.iter().format(", "))
+            None
+        });
+        let delay = departure
+            .scheduled
+            .map(|scheduled| round_minutes(departure.time - scheduled));
+        Self {
+            minutes: round_minutes(until),
+            realtime: departure.realtime,
+            status,
+            delay,
+            vehicle: departure.vehicle,
         }
     }
-}
 
-/// Number of minutes until an event
-#[derive(Debug)]
-pub struct Countdown(i64);
+    pub fn vehicle(&self) -> Option<Vehicle> {
+        self.vehicle
+    }
+
+    /// Minutes behind schedule, if it's a real-time prediction with a
+    /// schedule to compare to
+    pub fn delay(&self) -> Option<i64> {
+        self.delay.filter(|_| self.realtime && !self.is_cancelled())
+    }
+
+    pub fn lateness(&self) -> Option<Lateness> {
+        self.delay().map(|delay| {
+            if delay <= Lateness::ON_TIME_MAX {
+                Lateness::OnTime
+            } else if delay <= Lateness::LATE_MAX {
+                Lateness::Late
+            } else {
+                Lateness::VeryLate
+            }
+        })
+    }
+
+    pub fn is_realtime(&self) -> bool {
+        self.realtime
+    }
 
+    pub fn is_cancelled(&self) -> bool {
+        self.status == Some(Status::Cancelled)
+    }
+}
+
+/// Statuses are abbreviated, so two departures fit next to a stop name
 impl Display for Countdown {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}m", self.0)
+        match self.status {
+            Some(Status::Boarding) => write!(f, "BRD"),
+            Some(Status::Arriving) => write!(f, "ARR"),
+            // The MBTA shows "1 min" rather than spelling it out
+            Some(Status::Approaching) => write!(f, "1m"),
+            Some(Status::Delayed) => write!(f, "Delay"),
+            Some(Status::Cancelled) => write!(f, "Cancld"),
+            None => write!(f, "{}m", self.minutes),
+        }
     }
 }
 
-/// <https://api-v3.mbta.com/docs/swagger/index.html#/Prediction/ApiWeb_PredictionController_index>
-#[derive(Clone, Debug, Deserialize)]
-struct ApiPredictions {
-    data: Vec<Prediction>,
+/// Round to the nearest minute, like the MBTA's signs
+fn round_minutes(delta: TimeDelta) -> i64 {
+    (delta.num_seconds() + 30).div_euclid(60)
 }
 
-#[derive(Clone, Debug, Deserialize)]
-struct Prediction {
-    attributes: Attributes,
-    relationships: Relationships,
-}
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
 
-#[derive(Clone, Debug, Deserialize)]
-struct Attributes {
-    departure_time: Option<DateTime<Utc>>,
-}
+    /// Build predictions for a line, with a list of departures (minutes from
+    /// now) for each stop
+    pub(crate) fn line(name: &str, stops: &[&[i64]]) -> LinePredictions {
+        let stops = stops
+            .iter()
+            .enumerate()
+            .map(|(i, minutes)| {
+                let stop = Stop {
+                    name: format!("{name}-{i}"),
+                    id: format!("{name}-{i}"),
+                    walk_minutes: None,
+                };
+                let departures = minutes
+                    .iter()
+                    .map(|minutes| departure(minutes * 60, true))
+                    .collect();
+                StopPredictions::new(&stop, departures, now())
+            })
+            .collect();
+        LinePredictions {
+            name: name.into(),
+            stops,
+            alerts: Vec::new(),
+        }
+    }
 
-#[derive(Clone, Debug, Deserialize)]
-struct Relationships {
-    route: Relationship,
-    stop: Relationship,
-}
+    fn alert() -> Alert {
+        Alert {
+            effect: "Delay".into(),
+            header: String::new(),
+            severity: 3,
+        }
+    }
 
-#[derive(Clone, Debug, Deserialize)]
-struct Relationship {
-    data: RelationshipData,
-}
+    /// Get line names, and stop names for each line
+    fn names(predictions: &TransitPredictions) -> Vec<(&str, Vec<&str>)> {
+        predictions
+            .lines
+            .iter()
+            .map(|line| {
+                let stops =
+                    line.stops.iter().map(|stop| stop.name.as_str()).collect();
+                (line.name.as_str(), stops)
+            })
+            .collect()
+    }
 
-#[derive(Clone, Debug, Deserialize)]
-struct RelationshipData {
-    id: String,
+    /// Get displayed countdowns for each stop on each line
+    pub(super) fn countdowns(
+        predictions: &TransitPredictions,
+    ) -> Vec<Vec<Vec<String>>> {
+        predictions
+            .lines
+            .iter()
+            .map(|line| {
+                line.stops
+                    .iter()
+                    .map(|stop| {
+                        stop.predictions
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn departure(seconds: i64, realtime: bool) -> Departure {
+        Departure {
+            time: now() + TimeDelta::seconds(seconds),
+            arrival: None,
+            scheduled: None,
+            realtime,
+            status: None,
+            vehicle: None,
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        "2025-06-01T12:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn test_countdown_status() {
+        let countdown = |seconds: i64, realtime: bool| {
+            Countdown::new(departure(seconds, realtime), now()).to_string()
+        };
+        assert_eq!(countdown(-10, true), "BRD");
+        assert_eq!(countdown(0, true), "BRD");
+        assert_eq!(countdown(1, true), "ARR");
+        assert_eq!(countdown(30, true), "ARR");
+        assert_eq!(countdown(31, true), "1m");
+        assert_eq!(countdown(60, true), "1m");
+        assert_eq!(countdown(61, true), "1m");
+        assert_eq!(countdown(95, true), "2m");
+        // Schedules only count down
+        assert_eq!(countdown(0, false), "0m");
+        assert_eq!(countdown(20, false), "0m");
+        assert_eq!(countdown(45, false), "1m");
+    }
+
+    /// Status from the provider overrides the countdown, and arrival time
+    /// is preferred over departure
+    #[test]
+    fn test_countdown_provider_status() {
+        let countdown = Countdown::new(
+            Departure {
+                status: Some(Status::Delayed),
+                ..departure(600, true)
+            },
+            now(),
+        );
+        assert_eq!(countdown.to_string(), "Delay");
+        let countdown = Countdown::new(
+            Departure {
+                arrival: Some(now() + TimeDelta::seconds(20)),
+                ..departure(120, true)
+            },
+            now(),
+        );
+        assert_eq!(countdown.to_string(), "ARR");
+    }
+
+    #[test]
+    fn test_lateness() {
+        let countdown = |delay_seconds: i64, realtime: bool| {
+            let time = now() + TimeDelta::minutes(10);
+            Countdown::new(
+                Departure {
+                    scheduled: Some(time - TimeDelta::seconds(delay_seconds)),
+                    ..departure(600, realtime)
+                },
+                now(),
+            )
+        };
+        let lateness =
+            |delay_seconds| countdown(delay_seconds, true).lateness();
+        assert_eq!(lateness(-120), Some(Lateness::OnTime));
+        assert_eq!(lateness(60), Some(Lateness::OnTime));
+        // Rounds to 1 minute
+        assert_eq!(lateness(89), Some(Lateness::OnTime));
+        assert_eq!(lateness(90), Some(Lateness::Late));
+        assert_eq!(lateness(300), Some(Lateness::Late));
+        assert_eq!(lateness(360), Some(Lateness::VeryLate));
+        assert_eq!(countdown(360, true).delay(), Some(6));
+        // Only real-time departures can be late
+        assert_eq!(countdown(360, false).lateness(), None);
+        // Nothing to compare to
+        let countdown = Countdown::new(departure(600, true), now());
+        assert_eq!(countdown.lateness(), None);
+    }
+
+    #[test]
+    fn test_sort_by_departure() {
+        let mut predictions = TransitPredictions {
+            lines: vec![
+                line("a", &[&[], &[10]]),
+                line("b", &[&[]]),
+                line("c", &[&[3, 8]]),
+                line("d", &[&[5], &[2]]),
+                line("e", &[]),
+            ],
+        };
+        predictions.sort_by_departure();
+        // Empty stops and lines keep their order at the end
+        assert_eq!(
+            names(&predictions),
+            vec![
+                ("d", vec!["d-1", "d-0"]),
+                ("c", vec!["c-0"]),
+                ("a", vec!["a-1", "a-0"]),
+                ("b", vec!["b-0"]),
+                ("e", vec![]),
+            ]
+        );
+    }
+
+    /// Cancelled departures don't count toward the order
+    #[test]
+    fn test_sort_by_departure_cancelled() {
+        let mut cancelled = line("a", &[]);
+        let departure = Departure {
+            status: Some(Status::Cancelled),
+            ..departure(60, true)
+        };
+        cancelled.stops.push(StopPredictions::new(
+            &Stop {
+                name: "a-0".into(),
+                id: "a-0".into(),
+                walk_minutes: None,
+            },
+            vec![departure],
+            now(),
+        ));
+        let mut predictions = TransitPredictions {
+            lines: vec![cancelled, line("b", &[&[5]])],
+        };
+        predictions.sort_by_departure();
+        assert_eq!(
+            names(&predictions),
+            vec![("b", vec!["b-0"]), ("a", vec!["a-0"])]
+        );
+    }
+
+    #[test]
+    fn test_hide_empty() {
+        let mut alerted = line("c", &[&[]]);
+        alerted.alerts.push(alert());
+        let mut predictions = TransitPredictions {
+            lines: vec![
+                line("a", &[&[5], &[]]),
+                line("b", &[&[], &[]]),
+                alerted,
+                line("d", &[]),
+            ],
+        };
+        predictions.hide_empty();
+        // Lines with alerts are kept, even with no stops left
+        assert_eq!(
+            names(&predictions),
+            vec![("a", vec!["a-0"]), ("c", vec![])]
+        );
+    }
 }
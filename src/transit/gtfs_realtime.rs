@@ -0,0 +1,411 @@
+//! GTFS-Realtime, the standard format most agencies publish real-time
+//! predictions in. We only need the TripUpdates feed
+//!
+//! <https://gtfs.org/documentation/realtime/reference/>
+
+use crate::{
+    transit::{
+        Departure, LinePredictions, StopPredictions, TransitLine,
+        TransitPredictions, TransitProvider, gtfs_static::GtfsSchedule,
+    },
+    util::http_get_bytes,
+};
+use chrono::{DateTime, Local, Utc};
+use itertools::Itertools;
+use prost::Message;
+use std::collections::HashMap;
+use tracing::{error, info};
+
+/// Any agency's GTFS-Realtime TripUpdates feed. Stop and route IDs in the
+/// config must match the agency's static GTFS IDs
+#[derive(Debug)]
+pub struct GtfsRealtime {
+    url: String,
+    lines: Vec<TransitLine>,
+    /// Static timetable to use when the feed is unavailable
+    schedule: Option<GtfsSchedule>,
+}
+
+impl GtfsRealtime {
+    pub fn new(
+        url: &str,
+        lines: &[TransitLine],
+        schedule: Option<GtfsSchedule>,
+    ) -> Self {
+        Self {
+            url: url.to_owned(),
+            lines: lines.to_owned(),
+            schedule,
+        }
+    }
+
+    /// Decode a raw protobuf feed into predictions for the tracked lines
+    fn predictions(
+        &self,
+        feed: &[u8],
+        now: DateTime<Utc>,
+    ) -> Result<TransitPredictions, ()> {
+        let feed = proto::FeedMessage::decode(feed).map_err(|error| {
+            error!(%error, "Invalid GTFS-Realtime feed from {}", self.url)
+        })?;
+
+        // Group departures as {(route, stop): [departure]}
+        let mut grouped: HashMap<(&str, &str), Vec<Departure>> =
+            feed.entity
+                .iter()
+                .filter_map(|entity| entity.trip_update.as_ref())
+                .filter(|trip_update| !trip_update.trip.is_canceled())
+                .flat_map(|trip_update| {
+                    let route_id = trip_update.trip.route_id.as_deref();
+                    trip_update.stop_time_update.iter().filter_map(
+                        move |update| {
+                            let departure = update.departure_time()?;
+                            Some((
+                                (route_id?, update.stop_id.as_deref()?),
+                                departure,
+                            ))
+                        },
+                    )
+                })
+                .into_group_map();
+
+        let lines = self
+            .lines
+            .iter()
+            .map(|line| {
+                let stops = line
+                    .stops
+                    .iter()
+                    .map(|stop| {
+                        let departures = grouped
+                            .remove(&(line.route_id(), stop.id.as_str()))
+                            .unwrap_or_default();
+                        StopPredictions::new(stop, departures, now)
+                    })
+                    .collect();
+                LinePredictions {
+                    name: line.name.clone(),
+                    stops,
+                    // Alerts are a separate feed, which we don't read yet
+                    alerts: Vec::new(),
+                }
+            })
+            .collect();
+        Ok(TransitPredictions { lines })
+    }
+}
+
+impl TransitProvider for GtfsRealtime {
+    fn fetch(&mut self) -> Result<TransitPredictions, ()> {
+        let predictions = http_get_bytes(&self.url)
+            .and_then(|feed| self.predictions(&feed, Utc::now()));
+        match (predictions, &self.schedule) {
+            (Err(()), Some(schedule)) => {
+                info!("GTFS-Realtime feed unavailable, using static schedule");
+                Ok(schedule.predictions(Local::now()))
+            }
+            (predictions, _) => predictions,
+        }
+    }
+}
+
+/// The subset of the GTFS-Realtime schema that we read. Tags match the
+/// official proto file; everything else in the feed is skipped
+///
+/// <https://gtfs.org/documentation/realtime/proto/>
+mod proto {
+    use crate::transit::Departure;
+    use chrono::DateTime;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FeedMessage {
+        #[prost(message, required, tag = "1")]
+        pub header: FeedHeader,
+        #[prost(message, repeated, tag = "2")]
+        pub entity: Vec<FeedEntity>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FeedHeader {
+        #[prost(string, required, tag = "1")]
+        pub gtfs_realtime_version: String,
+        #[prost(uint64, optional, tag = "3")]
+        pub timestamp: Option<u64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FeedEntity {
+        #[prost(string, required, tag = "1")]
+        pub id: String,
+        #[prost(message, optional, tag = "3")]
+        pub trip_update: Option<TripUpdate>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TripUpdate {
+        #[prost(message, required, tag = "1")]
+        pub trip: TripDescriptor,
+        #[prost(message, repeated, tag = "2")]
+        pub stop_time_update: Vec<StopTimeUpdate>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TripDescriptor {
+        #[prost(string, optional, tag = "1")]
+        pub trip_id: Option<String>,
+        #[prost(enumeration = "TripScheduleRelationship", optional, tag = "4")]
+        pub schedule_relationship: Option<i32>,
+        #[prost(string, optional, tag = "5")]
+        pub route_id: Option<String>,
+    }
+
+    impl TripDescriptor {
+        pub fn is_canceled(&self) -> bool {
+            self.schedule_relationship
+                == Some(TripScheduleRelationship::Canceled as i32)
+        }
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct StopTimeUpdate {
+        #[prost(uint32, optional, tag = "1")]
+        pub stop_sequence: Option<u32>,
+        #[prost(message, optional, tag = "2")]
+        pub arrival: Option<StopTimeEvent>,
+        #[prost(message, optional, tag = "3")]
+        pub departure: Option<StopTimeEvent>,
+        #[prost(string, optional, tag = "4")]
+        pub stop_id: Option<String>,
+        #[prost(enumeration = "StopScheduleRelationship", optional, tag = "5")]
+        pub schedule_relationship: Option<i32>,
+    }
+
+    impl StopTimeUpdate {
+        /// When the vehicle leaves the stop. Falls back to arrival time, for
+        /// feeds that only give one. `None` if the stop is being skipped, or
+        /// the feed only gives a delay (which needs the static schedule)
+        pub fn departure_time(&self) -> Option<Departure> {
+            if self.schedule_relationship
+                == Some(StopScheduleRelationship::Skipped as i32)
+            {
+                return None;
+            }
+            let arrival = self.arrival.as_ref().and_then(|event| event.time);
+            let event = self
+                .departure
+                .as_ref()
+                .filter(|event| event.time.is_some())
+                .or(self.arrival.as_ref())?;
+            let time = event.time?;
+            Some(Departure {
+                time: DateTime::from_timestamp(time, 0)?,
+                arrival: arrival
+                    .and_then(|arrival| DateTime::from_timestamp(arrival, 0)),
+                // Delay is optional alongside a time
+                scheduled: event.delay.and_then(|delay| {
+                    DateTime::from_timestamp(time - i64::from(delay), 0)
+                }),
+                realtime: true,
+                status: None,
+                vehicle: None,
+            })
+        }
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct StopTimeEvent {
+        /// Seconds late relative to the schedule
+        #[prost(int32, optional, tag = "1")]
+        pub delay: Option<i32>,
+        /// Unix timestamp
+        #[prost(int64, optional, tag = "2")]
+        pub time: Option<i64>,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum TripScheduleRelationship {
+        Scheduled = 0,
+        Added = 1,
+        Unscheduled = 2,
+        Canceled = 3,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum StopScheduleRelationship {
+        Scheduled = 0,
+        Skipped = 1,
+        NoData = 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transit::{Countdown, tests::countdowns};
+    use proto::*;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_750_000_000, 0).unwrap()
+    }
+
+    /// A hand-built feed with one trip for each case we handle. Times are
+    /// seconds after [now]
+    fn feed() -> Vec<u8> {
+        let event = |seconds: i64| StopTimeEvent {
+            delay: None,
+            time: Some(now().timestamp() + seconds),
+        };
+        let update = |stop_id: &str| StopTimeUpdate {
+            stop_sequence: None,
+            arrival: None,
+            departure: None,
+            stop_id: Some(stop_id.into()),
+            schedule_relationship: None,
+        };
+        let trip = |id: &str, route_id: &str, updates| FeedEntity {
+            id: id.into(),
+            trip_update: Some(TripUpdate {
+                trip: TripDescriptor {
+                    trip_id: Some(id.into()),
+                    schedule_relationship: None,
+                    route_id: Some(route_id.into()),
+                },
+                stop_time_update: updates,
+            }),
+        };
+        let mut canceled = trip(
+            "canceled",
+            "1",
+            vec![StopTimeUpdate {
+                departure: Some(event(60)),
+                ..update("100")
+            }],
+        );
+        canceled
+            .trip_update
+            .as_mut()
+            .unwrap()
+            .trip
+            .schedule_relationship =
+            Some(TripScheduleRelationship::Canceled as i32);
+        let entity = vec![
+            trip(
+                "both",
+                "1",
+                vec![
+                    StopTimeUpdate {
+                        arrival: Some(event(290)),
+                        departure: Some(event(300)),
+                        ..update("100")
+                    },
+                    StopTimeUpdate {
+                        arrival: Some(event(600)),
+                        ..update("200")
+                    },
+                ],
+            ),
+            trip(
+                "departure_only",
+                "1",
+                vec![StopTimeUpdate {
+                    departure: Some(event(900)),
+                    ..update("100")
+                }],
+            ),
+            trip(
+                "skipped",
+                "1",
+                vec![StopTimeUpdate {
+                    departure: Some(event(120)),
+                    schedule_relationship: Some(
+                        StopScheduleRelationship::Skipped as i32,
+                    ),
+                    ..update("100")
+                }],
+            ),
+            canceled,
+            trip(
+                "other_route",
+                "2",
+                vec![StopTimeUpdate {
+                    departure: Some(event(480)),
+                    ..update("100")
+                }],
+            ),
+            trip(
+                "departed",
+                "1",
+                vec![StopTimeUpdate {
+                    departure: Some(event(-100)),
+                    ..update("100")
+                }],
+            ),
+            trip(
+                "delay_only",
+                "1",
+                vec![StopTimeUpdate {
+                    stop_sequence: Some(3),
+                    arrival: Some(StopTimeEvent {
+                        delay: Some(60),
+                        time: None,
+                    }),
+                    ..update("200")
+                }],
+            ),
+        ];
+        FeedMessage {
+            header: FeedHeader {
+                gtfs_realtime_version: "2.0".into(),
+                timestamp: Some(now().timestamp() as u64),
+            },
+            entity,
+        }
+        .encode_to_vec()
+    }
+
+    fn provider() -> GtfsRealtime {
+        let lines = serde_json::from_str::<Vec<TransitLine>>(
+            r#"[
+                {"name": "1", "stops": [
+                    {"name": "Main", "id": "100"},
+                    {"name": "Elm", "id": 200},
+                    {"name": "Nowhere", "id": "999"}
+                ]},
+                {"name": "Crosstown", "route_id": "2", "stops": [
+                    {"name": "Main", "id": "100"}
+                ]}
+            ]"#,
+        )
+        .unwrap();
+        GtfsRealtime::new("http://localhost/trip_updates.pb", &lines, None)
+    }
+
+    #[test]
+    fn test_predictions() {
+        let predictions = provider().predictions(&feed(), now()).unwrap();
+        assert_eq!(
+            countdowns(&predictions),
+            vec![
+                // Skipped, canceled, and departed trips are left out. A stop
+                // with only an arrival time uses the arrival. Stop 200's
+                // delay-only update has no time without the static schedule,
+                // so it's dropped
+                vec![vec!["5m", "15m"], vec!["10m"], vec![]],
+                vec![vec!["8m"]],
+            ]
+        );
+        assert!(
+            predictions.lines[0].stops[0]
+                .predictions
+                .iter()
+                .all(Countdown::is_realtime)
+        );
+    }
+
+    #[test]
+    fn test_invalid_feed() {
+        assert!(provider().predictions(b"not a protobuf", now()).is_err());
+    }
+}
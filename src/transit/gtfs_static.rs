@@ -0,0 +1,478 @@
+//! GTFS static schedules. Departures are computed from the timetable alone,
+//! for agencies without real-time data, or for when the network is down
+//!
+//! <https://gtfs.org/documentation/schedule/reference/>
+
+use crate::transit::{
+    Departure, LinePredictions, StopPredictions, TransitLine,
+    TransitPredictions, TransitProvider,
+};
+use chrono::{
+    DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeDelta, TimeZone,
+};
+use serde::{Deserialize, Deserializer, de::DeserializeOwned};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{Read, Seek},
+};
+use tracing::{error, info};
+use zip::ZipArchive;
+
+/// Timetable for the tracked stops, loaded from a GTFS zip. Stop and route
+/// IDs in the config must match the IDs in the zip
+#[derive(Debug)]
+pub struct GtfsSchedule {
+    lines: Vec<TransitLine>,
+    /// Scheduled times for each tracked (route, stop)
+    stop_times: HashMap<(String, String), Vec<StopTime>>,
+    /// Which days each service runs, by service ID
+    services: HashMap<String, Service>,
+}
+
+/// A single scheduled departure from a stop
+#[derive(Debug)]
+struct StopTime {
+    service_id: String,
+    /// Seconds after midnight on the service day. Can be more than 24 hours,
+    /// for trips that run past midnight
+    seconds: u32,
+}
+
+/// Days that a service runs
+#[derive(Debug, Default)]
+struct Service {
+    /// Regular weekly schedule, from `calendar.txt`
+    calendar: Option<Calendar>,
+    /// One-off exceptions, from `calendar_dates.txt`
+    added: HashSet<NaiveDate>,
+    removed: HashSet<NaiveDate>,
+}
+
+#[derive(Debug)]
+struct Calendar {
+    /// Monday first
+    weekdays: [bool; 7],
+    start: NaiveDate,
+    end: NaiveDate,
+}
+
+impl GtfsSchedule {
+    /// Load and index a GTFS zip. Only stop times for the tracked lines and
+    /// stops are kept, since a whole agency's timetable is huge
+    pub fn load(path: &str, lines: &[TransitLine]) -> Result<Self, ()> {
+        info!("Loading GTFS schedule from `{path}`");
+        let file = File::open(path).map_err(
+            |error| error!(%error, "Error opening GTFS schedule `{path}`"),
+        )?;
+        let mut archive = ZipArchive::new(file).map_err(
+            |error| error!(%error, "Invalid GTFS schedule `{path}`"),
+        )?;
+        Self::from_archive(&mut archive, lines)
+    }
+
+    fn from_archive<R: Read + Seek>(
+        archive: &mut ZipArchive<R>,
+        lines: &[TransitLine],
+    ) -> Result<Self, ()> {
+        let tracked: HashSet<(&str, &str)> = lines
+            .iter()
+            .flat_map(|line| {
+                line.stops
+                    .iter()
+                    .map(|stop| (line.route_id(), stop.id.as_str()))
+            })
+            .collect();
+
+        // Configured stops can be stations, in which case departures from
+        // any of their platforms count. Map each stop to the configured one
+        let mut stop_ids: HashMap<String, String> = tracked
+            .iter()
+            .map(|(_, stop_id)| (stop_id.to_string(), stop_id.to_string()))
+            .collect();
+        read_table(archive, "stops.txt", |row: StopRow| {
+            if let Some(parent) = row.parent_station
+                && tracked.iter().any(|(_, stop_id)| *stop_id == parent)
+            {
+                stop_ids.insert(row.stop_id, parent);
+            }
+        })?;
+
+        // {trip: (route, service)}, for tracked routes only
+        let mut trips: HashMap<String, (String, String)> = HashMap::new();
+        read_table(archive, "trips.txt", |row: TripRow| {
+            if tracked
+                .iter()
+                .any(|(route_id, _)| *route_id == row.route_id)
+            {
+                trips.insert(row.trip_id, (row.route_id, row.service_id));
+            }
+        })?;
+
+        let mut stop_times: HashMap<(String, String), Vec<StopTime>> =
+            HashMap::new();
+        read_table(archive, "stop_times.txt", |row: StopTimeRow| {
+            let Some((route_id, service_id)) = trips.get(&row.trip_id) else {
+                return;
+            };
+            let Some(stop_id) = stop_ids.get(&row.stop_id) else {
+                return;
+            };
+            // Non-timepoint stops are allowed to have no times
+            let Some(seconds) = row.departure_time.or(row.arrival_time) else {
+                return;
+            };
+            if tracked.contains(&(route_id.as_str(), stop_id.as_str())) {
+                stop_times
+                    .entry((route_id.clone(), stop_id.clone()))
+                    .or_default()
+                    .push(StopTime {
+                        service_id: service_id.clone(),
+                        seconds,
+                    });
+            }
+        })?;
+
+        // Either calendar file can be left out, but not both
+        let mut services: HashMap<String, Service> = HashMap::new();
+        if archive.index_for_name("calendar.txt").is_some() {
+            read_table(archive, "calendar.txt", |row: CalendarRow| {
+                services.entry(row.service_id).or_default().calendar =
+                    Some(Calendar {
+                        weekdays: [
+                            row.monday,
+                            row.tuesday,
+                            row.wednesday,
+                            row.thursday,
+                            row.friday,
+                            row.saturday,
+                            row.sunday,
+                        ]
+                        .map(|day| day == 1),
+                        start: row.start_date,
+                        end: row.end_date,
+                    });
+            })?;
+        }
+        if archive.index_for_name("calendar_dates.txt").is_some() {
+            read_table(
+                archive,
+                "calendar_dates.txt",
+                |row: CalendarDateRow| {
+                    let service = services.entry(row.service_id).or_default();
+                    match row.exception_type {
+                        1 => service.added.insert(row.date),
+                        _ => service.removed.insert(row.date),
+                    };
+                },
+            )?;
+        }
+
+        info!(
+            "Loaded {} scheduled stop times",
+            stop_times.values().map(Vec::len).sum::<usize>()
+        );
+        Ok(Self {
+            lines: lines.to_owned(),
+            stop_times,
+            services,
+        })
+    }
+
+    /// Get upcoming scheduled departures for all tracked lines. Times are
+    /// interpreted in `now`'s timezone, which should match the agency's
+    pub fn predictions<Tz: TimeZone>(
+        &self,
+        now: DateTime<Tz>,
+    ) -> TransitPredictions {
+        let lines = self
+            .lines
+            .iter()
+            .map(|line| {
+                let stops = line
+                    .stops
+                    .iter()
+                    .map(|stop| {
+                        StopPredictions::new(
+                            stop,
+                            self.departures(line.route_id(), &stop.id, &now),
+                            now.to_utc(),
+                        )
+                    })
+                    .collect();
+                LinePredictions {
+                    name: line.name.clone(),
+                    stops,
+                    alerts: Vec::new(),
+                }
+            })
+            .collect();
+        TransitPredictions { lines }
+    }
+
+    /// Get departure times from yesterday's, today's, and tomorrow's
+    /// services. Yesterday's can run past midnight, and tomorrow's are needed
+    /// late at night. Ones that already left get dropped later
+    fn departures<Tz: TimeZone>(
+        &self,
+        route_id: &str,
+        stop_id: &str,
+        now: &DateTime<Tz>,
+    ) -> Vec<Departure> {
+        let Some(stop_times) = self
+            .stop_times
+            .get(&(route_id.to_owned(), stop_id.to_owned()))
+        else {
+            return Vec::new();
+        };
+        let today = now.date_naive();
+        [today.pred_opt(), Some(today), today.succ_opt()]
+            .into_iter()
+            .flatten()
+            .flat_map(|date| {
+                // GTFS technically measures from noon minus 12 hours, which
+                // only differs on DST changeover days. Close enough
+                let midnight = now
+                    .timezone()
+                    .from_local_datetime(&date.and_time(NaiveTime::MIN))
+                    .earliest();
+                stop_times
+                    .iter()
+                    .filter(move |stop_time| {
+                        self.services
+                            .get(&stop_time.service_id)
+                            .is_some_and(|service| service.runs_on(date))
+                    })
+                    .filter_map(move |stop_time| {
+                        let time = midnight.clone()?
+                            + TimeDelta::seconds(stop_time.seconds.into());
+                        Some(Departure {
+                            time: time.to_utc(),
+                            arrival: None,
+                            scheduled: None,
+                            realtime: false,
+                            status: None,
+                            vehicle: None,
+                        })
+                    })
+            })
+            .collect()
+    }
+}
+
+impl TransitProvider for GtfsSchedule {
+    fn fetch(&mut self) -> Result<TransitPredictions, ()> {
+        Ok(self.predictions(Local::now()))
+    }
+}
+
+impl Service {
+    fn runs_on(&self, date: NaiveDate) -> bool {
+        if self.added.contains(&date) {
+            true
+        } else if self.removed.contains(&date) {
+            false
+        } else {
+            self.calendar.as_ref().is_some_and(|calendar| {
+                (calendar.start..=calendar.end).contains(&date)
+                    && calendar.weekdays
+                        [date.weekday().num_days_from_monday() as usize]
+            })
+        }
+    }
+}
+
+/// Parse each row of a CSV file in the zip. Any bad row fails the whole
+/// file, since a partial timetable would be misleading
+fn read_table<R: Read + Seek, T: DeserializeOwned>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+    mut f: impl FnMut(T),
+) -> Result<(), ()> {
+    let file = archive
+        .by_name(name)
+        .map_err(|error| error!(%error, "Error reading `{name}` from GTFS"))?;
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(file);
+    for row in reader.deserialize() {
+        let row =
+            row.map_err(|error| error!(%error, "Invalid row in `{name}`"))?;
+        f(row);
+    }
+    Ok(())
+}
+
+/// Parse a `YYYYMMDD` date
+fn deserialize_date<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<NaiveDate, D::Error> {
+    let date = String::deserialize(deserializer)?;
+    NaiveDate::parse_from_str(&date, "%Y%m%d").map_err(serde::de::Error::custom)
+}
+
+/// Parse an `HH:MM:SS` time into seconds since midnight. Hours can go past
+/// 24. Empty means no time
+fn deserialize_time<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u32>, D::Error> {
+    let time = String::deserialize(deserializer)?;
+    if time.is_empty() {
+        return Ok(None);
+    }
+    let seconds = time
+        .split(':')
+        .map(str::parse::<u32>)
+        .collect::<Result<Vec<_>, _>>()
+        .ok()
+        .and_then(|parts| match parts.as_slice() {
+            [hours, minutes, seconds] => {
+                Some(hours * 3600 + minutes * 60 + seconds)
+            }
+            _ => None,
+        })
+        .ok_or_else(|| {
+            serde::de::Error::custom(format!("Invalid time `{time}`"))
+        })?;
+    Ok(Some(seconds))
+}
+
+#[derive(Deserialize)]
+struct StopRow {
+    stop_id: String,
+    #[serde(default)]
+    parent_station: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TripRow {
+    route_id: String,
+    service_id: String,
+    trip_id: String,
+}
+
+#[derive(Deserialize)]
+struct StopTimeRow {
+    trip_id: String,
+    stop_id: String,
+    #[serde(default, deserialize_with = "deserialize_time")]
+    arrival_time: Option<u32>,
+    #[serde(default, deserialize_with = "deserialize_time")]
+    departure_time: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct CalendarRow {
+    service_id: String,
+    monday: u8,
+    tuesday: u8,
+    wednesday: u8,
+    thursday: u8,
+    friday: u8,
+    saturday: u8,
+    sunday: u8,
+    #[serde(deserialize_with = "deserialize_date")]
+    start_date: NaiveDate,
+    #[serde(deserialize_with = "deserialize_date")]
+    end_date: NaiveDate,
+}
+
+#[derive(Deserialize)]
+struct CalendarDateRow {
+    service_id: String,
+    #[serde(deserialize_with = "deserialize_date")]
+    date: NaiveDate,
+    /// 1 for added, 2 for removed
+    exception_type: u8,
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::transit::{Countdown, tests::countdowns};
+    use chrono::Utc;
+    use std::io::{Cursor, Write};
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    /// Hand-written tables in `tests/fixtures/gtfs_static`, with one trip for
+    /// each case we handle. Services run on [now], a Monday
+    const TABLES: &[(&str, &str)] = &[
+        (
+            "stops.txt",
+            include_str!("../../tests/fixtures/gtfs_static/stops.txt"),
+        ),
+        (
+            "routes.txt",
+            include_str!("../../tests/fixtures/gtfs_static/routes.txt"),
+        ),
+        (
+            "trips.txt",
+            include_str!("../../tests/fixtures/gtfs_static/trips.txt"),
+        ),
+        (
+            "stop_times.txt",
+            include_str!("../../tests/fixtures/gtfs_static/stop_times.txt"),
+        ),
+        (
+            "calendar.txt",
+            include_str!("../../tests/fixtures/gtfs_static/calendar.txt"),
+        ),
+        (
+            "calendar_dates.txt",
+            include_str!("../../tests/fixtures/gtfs_static/calendar_dates.txt"),
+        ),
+    ];
+
+    fn now() -> DateTime<Utc> {
+        "2025-06-16T08:00:00Z".parse().unwrap()
+    }
+
+    pub(crate) fn schedule() -> GtfsSchedule {
+        let lines = serde_json::from_str::<Vec<TransitLine>>(
+            r#"[
+                {"name": "1", "stops": [
+                    {"name": "Main", "id": "place-main"},
+                    {"name": "Elm", "id": "elm"}
+                ]},
+                {"name": "Crosstown", "route_id": "2", "stops": [
+                    {"name": "Main", "id": "place-main"}
+                ]}
+            ]"#,
+        )
+        .unwrap();
+        // Zip up the tables, like a real GTFS download
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in TABLES {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        let zip = zip.finish().unwrap();
+        let mut archive = ZipArchive::new(zip).unwrap();
+        GtfsSchedule::from_archive(&mut archive, &lines).unwrap()
+    }
+
+    #[test]
+    fn test_predictions() {
+        let predictions = schedule().predictions(now());
+        assert_eq!(
+            countdowns(&predictions),
+            vec![
+                // Platforms count toward their station. Includes last night's
+                // service running past midnight and an added holiday service;
+                // weekend service is left out
+                vec![vec!["5m", "9m"], vec!["15m", "30m"]],
+                // Arrival time is used when there's no departure time. The
+                // removed service is left out today, but runs tomorrow
+                vec![vec!["12m", "1441m"]],
+            ]
+        );
+        assert!(
+            predictions
+                .lines
+                .iter()
+                .flat_map(|line| &line.stops)
+                .flat_map(|stop| stop.predictions.iter())
+                .all(|countdown| !Countdown::is_realtime(countdown))
+        );
+    }
+}
@@ -0,0 +1,1008 @@
+mod stream;
+
+use crate::{
+    transit::{
+        Alert, Crowding, DATA_TTL, Departure, LinePredictions, Status,
+        StopPredictions, TransitLine, TransitPredictions, TransitProvider,
+        Vehicle, gtfs_static::GtfsSchedule,
+    },
+    util::http_get,
+};
+use chrono::{
+    DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Utc,
+};
+use itertools::Itertools;
+use serde::Deserialize;
+use std::{cmp::Reverse, collections::HashMap, time::Instant};
+use tracing::info;
+
+const API_HOST: &str = "https://api-v3.mbta.com";
+/// Local hour when one service day ends and the next starts. Late-night
+/// trips belong to the previous day's schedule
+const SERVICE_DAY_START: u32 = 3;
+
+/// MBTA v3 API. Predictions are streamed, with polling as a fallback
+///
+/// <https://www.mbta.com/developers/v3-api>
+#[derive(Debug)]
+pub struct Mbta {
+    lines: Vec<TransitLine>,
+    /// Comma-separated list of all tracked stop IDs
+    stop_ids: String,
+    route_alerts_url: String,
+    stop_alerts_url: String,
+    vehicles_url: String,
+    /// Alerts, schedules, and vehicles are polled even while predictions are
+    /// streaming. If a request fails, we keep showing the last data we got
+    /// rather than making it flicker away
+    alerts: ApiAlerts,
+    schedules: ApiPredictions,
+    vehicles: ApiVehicles,
+    /// Sorted stop sequences for each trip with a vehicle on it, to count
+    /// how many stops away the vehicle is. Sequences can skip numbers, so
+    /// they can't just be subtracted
+    trip_stops: HashMap<String, Vec<u32>>,
+    /// When alerts, schedules, and vehicles were last fetched
+    fetched_at: Option<Instant>,
+    /// Static timetable to use when the API is unavailable
+    schedule: Option<GtfsSchedule>,
+    /// When we last got predictions, from either polling or the stream
+    updated_at: Option<Instant>,
+}
+
+impl Mbta {
+    pub fn new(lines: &[TransitLine], schedule: Option<GtfsSchedule>) -> Self {
+        let stop_ids = lines
+            .iter()
+            .flat_map(|line| &line.stops)
+            .map(|stop| &stop.id)
+            .join(",");
+        let route_ids = lines.iter().map(TransitLine::route_id).join(",");
+        // The API ANDs the route and stop filters together, so fetch alerts
+        // for each separately and filter down to our stops after the fact
+        let route_alerts_url =
+            format!("{API_HOST}/alerts?filter[route]={route_ids}");
+        let stop_alerts_url =
+            format!("{API_HOST}/alerts?filter[stop]={stop_ids}");
+        let vehicles_url =
+            format!("{API_HOST}/vehicles?filter[route]={route_ids}");
+        Self {
+            lines: lines.to_owned(),
+            stop_ids,
+            route_alerts_url,
+            stop_alerts_url,
+            vehicles_url,
+            alerts: ApiAlerts::default(),
+            schedules: ApiPredictions::default(),
+            vehicles: ApiVehicles::default(),
+            trip_stops: HashMap::new(),
+            fetched_at: None,
+            schedule,
+            updated_at: None,
+        }
+    }
+
+    fn predictions_url(&self) -> String {
+        // Include each prediction's schedule, to see how late it's running
+        format!(
+            "{API_HOST}/predictions?filter[stop]={}&include=schedule",
+            self.stop_ids
+        )
+    }
+
+    /// Re-fetch alerts, schedules, vehicles, and stop sequences for trips in
+    /// the predictions, if they're older than [DATA_TTL]
+    fn refresh(&mut self, predictions: &ApiPredictions) {
+        if self
+            .fetched_at
+            .is_some_and(|fetched_at| fetched_at.elapsed() < DATA_TTL)
+        {
+            return;
+        }
+        if let (Ok(route_alerts), Ok(stop_alerts)) = (
+            http_get::<ApiAlerts>(&self.route_alerts_url),
+            http_get::<ApiAlerts>(&self.stop_alerts_url),
+        ) {
+            self.alerts = route_alerts.merge(stop_alerts);
+        }
+        // Only fetch schedules from now on. Otherwise we get the whole day
+        let (date, min_time) = service_time(Local::now().naive_local());
+        let schedules_url = format!(
+            "{API_HOST}/schedules?filter[stop]={}&filter[date]={date}\
+            &filter[min_time]={min_time}",
+            self.stop_ids,
+        );
+        if let Ok(schedules) = http_get::<ApiPredictions>(&schedules_url) {
+            self.schedules = schedules;
+        }
+        if let Ok(vehicles) = http_get::<ApiVehicles>(&self.vehicles_url) {
+            self.vehicles = vehicles;
+        }
+        self.load_trip_stops(predictions);
+        self.fetched_at = Some(Instant::now());
+    }
+
+    /// Scheduled times from the static timetable, for when the API is down.
+    /// The last predictions stay up until they're older than [DATA_TTL], so
+    /// a single failed request doesn't swap them out
+    fn fallback(&self) -> Result<TransitPredictions, ()> {
+        let schedule = self.schedule.as_ref().ok_or(())?;
+        if self
+            .updated_at
+            .is_some_and(|updated_at| updated_at.elapsed() < DATA_TTL)
+        {
+            return Err(());
+        }
+        info!("MBTA API unavailable, using static schedule");
+        Ok(schedule.predictions(Local::now()))
+    }
+
+    /// Fetch stop sequences for any new trips that have a vehicle on them.
+    /// Trips that are done are dropped
+    fn load_trip_stops(&mut self, predictions: &ApiPredictions) {
+        let trip_ids: Vec<&str> = predictions
+            .data
+            .iter()
+            .filter(|prediction| prediction.relationships.vehicle().is_some())
+            .filter_map(|prediction| prediction.relationships.trip())
+            .unique()
+            .collect();
+        self.trip_stops
+            .retain(|trip_id, _| trip_ids.contains(&trip_id.as_str()));
+        let new = trip_ids
+            .iter()
+            .filter(|trip_id| !self.trip_stops.contains_key(**trip_id))
+            .join(",");
+        if new.is_empty() {
+            return;
+        }
+        let url = format!("{API_HOST}/schedules?filter[trip]={new}");
+        let Ok(schedules) = http_get::<ApiPredictions>(&url) else {
+            return;
+        };
+        for schedule in &schedules.data {
+            if let (Some(trip_id), Some(stop_sequence)) = (
+                schedule.relationships.trip(),
+                schedule.attributes.stop_sequence,
+            ) {
+                self.trip_stops
+                    .entry(trip_id.to_owned())
+                    .or_default()
+                    .push(stop_sequence);
+            }
+        }
+        for stops in self.trip_stops.values_mut() {
+            stops.sort_unstable();
+            stops.dedup();
+        }
+    }
+
+    /// Combine raw predictions with the latest alerts, schedules, and
+    /// vehicles
+    fn predictions(&self, predictions: &ApiPredictions) -> TransitPredictions {
+        TransitPredictions::from_response(
+            &self.lines,
+            predictions,
+            &self.schedules,
+            &self.vehicles,
+            &self.trip_stops,
+            &self.alerts,
+        )
+    }
+}
+
+impl TransitProvider for Mbta {
+    fn fetch(&mut self) -> Result<TransitPredictions, ()> {
+        let Ok(predictions) = http_get(&self.predictions_url()) else {
+            return self.fallback();
+        };
+        self.updated_at = Some(Instant::now());
+        self.refresh(&predictions);
+        Ok(self.predictions(&predictions))
+    }
+
+    fn stream(&mut self, on_update: &mut dyn FnMut(TransitPredictions)) {
+        stream::stream_predictions(self, on_update);
+        info!("Falling back to polling for transit predictions");
+    }
+}
+
+impl TransitPredictions {
+    /// Gather predictions, schedules, vehicles, and active alerts from the
+    /// API responses. Predictions are preferred, but if a trip only has a
+    /// schedule we'll show that instead
+    fn from_response(
+        lines: &[TransitLine],
+        predictions: &ApiPredictions,
+        schedules: &ApiPredictions,
+        vehicles: &ApiVehicles,
+        trip_stops: &HashMap<String, Vec<u32>>,
+        api_alerts: &ApiAlerts,
+    ) -> TransitPredictions {
+        let now = Utc::now();
+        // Group API data as {(line, stop): {trip: departure}}. Schedules go
+        // in first, then real-time predictions replace them trip-by-trip
+        let mut grouped: HashMap<(&str, &str), HashMap<&str, Departure>> =
+            HashMap::new();
+        // Schedules included with the predictions, by ID. These cover trips
+        // that are running late, which the separate schedule request misses
+        let included: HashMap<&str, DateTime<Utc>> = predictions
+            .included
+            .iter()
+            .filter_map(|schedule| {
+                Some((
+                    schedule.id.as_str(),
+                    schedule.attributes.departure_time?,
+                ))
+            })
+            .collect();
+        let scheduled = schedules.data.iter().map(|schedule| (schedule, false));
+        let predicted =
+            predictions.data.iter().map(|prediction| (prediction, true));
+        for (departure, realtime) in scheduled.chain(predicted) {
+            let route_id = departure.relationships.route.data.id.as_str();
+            let stop_id = departure.relationships.stop.data.id.as_str();
+            let trips = grouped.entry((route_id, stop_id)).or_default();
+            // Without a trip there's nothing to merge on. Fall back to the
+            // record's own ID so it's still shown
+            let trip_id = departure
+                .relationships
+                .trip()
+                .unwrap_or(departure.id.as_str());
+            let attributes = &departure.attributes;
+            let scheduled = if realtime {
+                departure
+                    .relationships
+                    .schedule
+                    .as_ref()
+                    .and_then(|schedule| schedule.data.as_ref())
+                    .and_then(|data| included.get(data.id.as_str()))
+                    .copied()
+                    .or_else(|| trips.get(trip_id)?.scheduled)
+            } else {
+                // A schedule is its own scheduled time
+                attributes.departure_time
+            };
+            match (attributes.schedule_relationship, attributes.departure_time)
+            {
+                // Keep showing the scheduled time, so it's clear the trip
+                // isn't coming
+                (Some(ScheduleRelationship::Cancelled), _) => {
+                    if let Some(time) = scheduled {
+                        let departure = Departure {
+                            time,
+                            arrival: None,
+                            scheduled,
+                            realtime,
+                            status: Some(Status::Cancelled),
+                            vehicle: None,
+                        };
+                        trips.insert(trip_id, departure);
+                    }
+                }
+                // No prediction available, so stick with the schedule
+                (Some(ScheduleRelationship::NoData), _) => {}
+                // Departure time will be empty if the stop is being skipped,
+                // so drop the scheduled departure too
+                (Some(ScheduleRelationship::Skipped), _) | (_, None) => {
+                    trips.remove(trip_id);
+                }
+                (_, Some(time)) => {
+                    let departure = Departure {
+                        time,
+                        arrival: attributes.arrival_time,
+                        scheduled,
+                        realtime,
+                        status: attributes
+                            .status
+                            .as_deref()
+                            .and_then(parse_status),
+                        vehicle: vehicles.find(departure, trip_stops),
+                    };
+                    trips.insert(trip_id, departure);
+                }
+            }
+        }
+
+        let lines = lines
+            .iter()
+            .map(|line| {
+                let stops = line
+                    .stops
+                    .iter()
+                    .map(|stop| {
+                        let departures = grouped
+                            .remove(&(line.route_id(), stop.id.as_str()))
+                            .unwrap_or_default()
+                            .into_values()
+                            .collect();
+                        StopPredictions::new(stop, departures, now)
+                    })
+                    .collect();
+                let alerts = api_alerts
+                    .data
+                    .iter()
+                    .filter(|alert| {
+                        alert.attributes.is_active(now)
+                            && alert.attributes.affects(line)
+                    })
+                    .map(|alert| Alert::from(&alert.attributes))
+                    // Most severe first
+                    .sorted_by_key(|alert| Reverse(alert.severity))
+                    .collect();
+                LinePredictions {
+                    name: line.name.clone(),
+                    stops,
+                    alerts,
+                }
+            })
+            .collect();
+        TransitPredictions { lines }
+    }
+}
+
+/// Get the service date and time of day for a local time, in the format the
+/// schedules API filters on. Service days run past midnight, until
+/// [SERVICE_DAY_START], and times after midnight count up from 24:00
+fn service_time(now: NaiveDateTime) -> (NaiveDate, String) {
+    let date = (now - TimeDelta::hours(SERVICE_DAY_START.into())).date();
+    let since_midnight = now - date.and_time(NaiveTime::MIN);
+    let time = format!(
+        "{:02}:{:02}",
+        since_midnight.num_hours(),
+        since_midnight.num_minutes() % 60
+    );
+    (date, time)
+}
+
+/// Map the free-text prediction status onto the ones we display. Anything
+/// else (e.g. "Stopped 3 stops away") is left to the countdown
+fn parse_status(status: &str) -> Option<Status> {
+    match status.to_lowercase().as_str() {
+        "boarding" | "now boarding" | "stopped at station" => {
+            Some(Status::Boarding)
+        }
+        "arriving" => Some(Status::Arriving),
+        "approaching" => Some(Status::Approaching),
+        "delayed" => Some(Status::Delayed),
+        "cancelled" | "canceled" => Some(Status::Cancelled),
+        _ => None,
+    }
+}
+
+/// Convert the API's SCREAMING_SNAKE_CASE effect to sentence case
+impl From<&AlertAttributes> for Alert {
+    fn from(attributes: &AlertAttributes) -> Self {
+        let mut effect = attributes.effect.replace('_', " ").to_lowercase();
+        if let Some(first) = effect.get_mut(..1) {
+            first.make_ascii_uppercase();
+        }
+        Self {
+            effect,
+            header: attributes.header.clone(),
+            severity: attributes.severity,
+        }
+    }
+}
+
+/// <https://api-v3.mbta.com/docs/swagger/index.html#/Prediction/ApiWeb_PredictionController_index>
+///
+/// Schedules have the same shape, for the fields we care about:
+/// <https://api-v3.mbta.com/docs/swagger/index.html#/Schedule/ApiWeb_ScheduleController_index>
+#[derive(Clone, Debug, Default, Deserialize)]
+struct ApiPredictions {
+    data: Vec<Prediction>,
+    /// Schedules for the predictions, from `include=schedule`
+    #[serde(default)]
+    included: Vec<Prediction>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Prediction {
+    id: String,
+    /// `prediction` or `schedule`
+    #[serde(rename = "type", default)]
+    kind: String,
+    attributes: Attributes,
+    relationships: Relationships,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Attributes {
+    /// Position of the stop in the trip. Not necessarily consecutive
+    #[serde(default)]
+    stop_sequence: Option<u32>,
+    #[serde(default)]
+    arrival_time: Option<DateTime<Utc>>,
+    departure_time: Option<DateTime<Utc>>,
+    /// Free text, e.g. "Boarding" or "Delayed". Predictions only
+    #[serde(default)]
+    status: Option<String>,
+    /// `None` for regularly scheduled trips. Predictions only
+    #[serde(default)]
+    schedule_relationship: Option<ScheduleRelationship>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum ScheduleRelationship {
+    Added,
+    Cancelled,
+    NoData,
+    Skipped,
+    Unscheduled,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Relationships {
+    route: Relationship,
+    stop: Relationship,
+    trip: Option<OptionalRelationship>,
+    /// Predictions only
+    #[serde(default)]
+    schedule: Option<OptionalRelationship>,
+    /// Predictions only
+    #[serde(default)]
+    vehicle: Option<OptionalRelationship>,
+}
+
+impl Relationships {
+    fn trip(&self) -> Option<&str> {
+        Some(self.trip.as_ref()?.data.as_ref()?.id.as_str())
+    }
+
+    fn vehicle(&self) -> Option<&str> {
+        Some(self.vehicle.as_ref()?.data.as_ref()?.id.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Relationship {
+    data: RelationshipData,
+}
+
+/// A relationship whose data may be `null`
+#[derive(Clone, Debug, Deserialize)]
+struct OptionalRelationship {
+    data: Option<RelationshipData>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct RelationshipData {
+    id: String,
+}
+
+/// <https://api-v3.mbta.com/docs/swagger/index.html#/Vehicle/ApiWeb_VehicleController_index>
+#[derive(Clone, Debug, Default, Deserialize)]
+struct ApiVehicles {
+    data: Vec<ApiVehicle>,
+}
+
+impl ApiVehicles {
+    /// Get live info on the vehicle serving a prediction. The vehicle may
+    /// still be finishing an earlier trip, in which case its position tells
+    /// us nothing
+    fn find(
+        &self,
+        prediction: &Prediction,
+        trip_stops: &HashMap<String, Vec<u32>>,
+    ) -> Option<Vehicle> {
+        let vehicle_id = prediction.relationships.vehicle()?;
+        let trip_id = prediction.relationships.trip()?;
+        let vehicle =
+            self.data.iter().find(|vehicle| vehicle.id == vehicle_id)?;
+        if vehicle.relationships.trip.data.as_ref()?.id != trip_id {
+            return None;
+        }
+        let attributes = &vehicle.attributes;
+        // Count stops from the vehicle's current one up to and including
+        // ours. If it's already stopped at the current one, that one's done
+        let stops_away = trip_stops.get(trip_id).and_then(|stops| {
+            let mut current = attributes.current_stop_sequence?;
+            if attributes.current_status == Some(CurrentStatus::StoppedAt) {
+                current += 1;
+            }
+            let ours = prediction.attributes.stop_sequence?;
+            let count = stops
+                .iter()
+                .filter(|stop| (current..=ours).contains(*stop))
+                .count();
+            u32::try_from(count).ok()
+        });
+        // Trains report each car separately. Go by the emptiest one, since
+        // that's the one to get on
+        let crowding = attributes
+            .occupancy_status
+            .and_then(Occupancy::crowding)
+            .or_else(|| {
+                attributes
+                    .carriages
+                    .iter()
+                    .filter_map(|carriage| {
+                        carriage.occupancy_status?.crowding()
+                    })
+                    .min()
+            });
+        Some(Vehicle {
+            stops_away,
+            crowding,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ApiVehicle {
+    id: String,
+    attributes: VehicleAttributes,
+    relationships: VehicleRelationships,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct VehicleAttributes {
+    /// Position in the trip of the stop the vehicle is at or heading to
+    current_stop_sequence: Option<u32>,
+    /// Whether the vehicle is at the current stop or on its way there
+    #[serde(default)]
+    current_status: Option<CurrentStatus>,
+    occupancy_status: Option<Occupancy>,
+    /// Per-car occupancy, for trains
+    #[serde(default)]
+    carriages: Vec<Carriage>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum CurrentStatus {
+    IncomingAt,
+    StoppedAt,
+    InTransitTo,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Carriage {
+    occupancy_status: Option<Occupancy>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct VehicleRelationships {
+    trip: OptionalRelationship,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum Occupancy {
+    Empty,
+    ManySeatsAvailable,
+    FewSeatsAvailable,
+    StandingRoomOnly,
+    CrushedStandingRoomOnly,
+    Full,
+    NotAcceptingPassengers,
+    #[serde(other)]
+    Unknown,
+}
+
+impl Occupancy {
+    fn crowding(self) -> Option<Crowding> {
+        match self {
+            Self::Empty | Self::ManySeatsAvailable => {
+                Some(Crowding::NotCrowded)
+            }
+            Self::FewSeatsAvailable => Some(Crowding::SomewhatCrowded),
+            Self::StandingRoomOnly
+            | Self::CrushedStandingRoomOnly
+            | Self::Full
+            | Self::NotAcceptingPassengers => Some(Crowding::Crowded),
+            Self::Unknown => None,
+        }
+    }
+}
+
+/// <https://api-v3.mbta.com/docs/swagger/index.html#/Alert/ApiWeb_AlertController_index>
+#[derive(Clone, Debug, Default, Deserialize)]
+struct ApiAlerts {
+    data: Vec<ApiAlert>,
+}
+
+impl ApiAlerts {
+    /// Combine two lists of alerts. Alerts on both routes and stops show up
+    /// in both lists, so drop duplicates
+    fn merge(self, other: Self) -> Self {
+        let data = self
+            .data
+            .into_iter()
+            .chain(other.data)
+            .unique_by(|alert| alert.id.clone())
+            .collect();
+        Self { data }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ApiAlert {
+    id: String,
+    attributes: AlertAttributes,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct AlertAttributes {
+    /// e.g. "SUSPENSION", "SHUTTLE", "DELAY"
+    effect: String,
+    header: String,
+    severity: u8,
+    active_period: Vec<ActivePeriod>,
+    informed_entity: Vec<InformedEntity>,
+}
+
+impl AlertAttributes {
+    /// Is the alert in effect at the given time?
+    fn is_active(&self, time: DateTime<Utc>) -> bool {
+        self.active_period.iter().any(|period| {
+            period.start <= time && period.end.is_none_or(|end| time < end)
+        })
+    }
+
+    /// Does the alert apply to the line, or any of its tracked stops?
+    fn affects(&self, line: &TransitLine) -> bool {
+        let has_stop =
+            |stop_id: &str| line.stops.iter().any(|stop| stop.id == stop_id);
+        self.informed_entity.iter().any(|entity| {
+            match (entity.route.as_deref(), entity.stop.as_deref()) {
+                // No stop means the whole route is affected
+                (Some(route_id), None) => route_id == line.route_id(),
+                (Some(route_id), Some(stop_id)) => {
+                    route_id == line.route_id() && has_stop(stop_id)
+                }
+                // Stop-only alerts (e.g. a closed entrance) apply to every
+                // route serving the stop
+                (None, Some(stop_id)) => has_stop(stop_id),
+                (None, None) => false,
+            }
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ActivePeriod {
+    start: DateTime<Utc>,
+    /// `None` means until further notice
+    end: Option<DateTime<Utc>>,
+}
+
+/// Something an alert applies to. Any combination of fields can be present
+#[derive(Clone, Debug, Deserialize)]
+struct InformedEntity {
+    route: Option<String>,
+    stop: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transit::gtfs_static;
+    use serde_json::json;
+
+    #[test]
+    fn test_urls() {
+        let lines: Vec<TransitLine> = serde_json::from_value(json!([
+            {"name": "Red", "stops": [{"name": "Park", "id": "place-pktrm"}]},
+            {"name": "86", "stops": [{"name": "Union", "id": "2374"}]},
+        ]))
+        .unwrap();
+        let mbta = Mbta::new(&lines, None);
+        assert_eq!(
+            mbta.route_alerts_url,
+            format!("{API_HOST}/alerts?filter[route]=Red,86")
+        );
+        assert_eq!(
+            mbta.stop_alerts_url,
+            format!("{API_HOST}/alerts?filter[stop]=place-pktrm,2374")
+        );
+        assert_eq!(
+            mbta.vehicles_url,
+            format!("{API_HOST}/vehicles?filter[route]=Red,86")
+        );
+    }
+
+    #[test]
+    fn test_service_time() {
+        let service_time = |time: &str| {
+            let (date, time) = service_time(time.parse().unwrap());
+            (date.to_string(), time)
+        };
+        assert_eq!(
+            service_time("2025-06-01T08:05:00"),
+            ("2025-06-01".into(), "08:05".into())
+        );
+        assert_eq!(
+            service_time("2025-06-01T23:59:00"),
+            ("2025-06-01".into(), "23:59".into())
+        );
+        // After midnight is still the previous service day
+        assert_eq!(
+            service_time("2025-06-02T00:30:00"),
+            ("2025-06-01".into(), "24:30".into())
+        );
+        assert_eq!(
+            service_time("2025-06-02T02:59:00"),
+            ("2025-06-01".into(), "26:59".into())
+        );
+        assert_eq!(
+            service_time("2025-06-02T03:00:00"),
+            ("2025-06-02".into(), "03:00".into())
+        );
+    }
+
+    /// The vehicle is at or heading to stop sequence 2, and we're at 4
+    #[test]
+    fn test_vehicle_stops_away() {
+        let prediction: Prediction = serde_json::from_value(json!({
+            "id": "prediction",
+            "attributes": {"stop_sequence": 4, "departure_time": null},
+            "relationships": {
+                "route": {"data": {"id": "86"}},
+                "stop": {"data": {"id": "2374"}},
+                "trip": {"data": {"id": "trip"}},
+                "vehicle": {"data": {"id": "bus"}},
+            },
+        }))
+        .unwrap();
+        let trip_stops = HashMap::from([("trip".into(), vec![1, 2, 3, 4, 5])]);
+        let stops_away = |status: &str| {
+            let vehicles: ApiVehicles = serde_json::from_value(json!({
+                "data": [{
+                    "id": "bus",
+                    "attributes": {
+                        "current_stop_sequence": 2,
+                        "current_status": status,
+                        "occupancy_status": null,
+                    },
+                    "relationships": {"trip": {"data": {"id": "trip"}}},
+                }],
+            }))
+            .unwrap();
+            vehicles.find(&prediction, &trip_stops)?.stops_away
+        };
+        assert_eq!(stops_away("STOPPED_AT"), Some(2));
+        assert_eq!(stops_away("INCOMING_AT"), Some(3));
+        assert_eq!(stops_away("IN_TRANSIT_TO"), Some(3));
+    }
+
+    #[test]
+    fn test_parse_status() {
+        assert_eq!(parse_status("Boarding"), Some(Status::Boarding));
+        assert_eq!(parse_status("Now boarding"), Some(Status::Boarding));
+        assert_eq!(parse_status("Stopped at station"), Some(Status::Boarding));
+        assert_eq!(parse_status("Arriving"), Some(Status::Arriving));
+        assert_eq!(parse_status("APPROACHING"), Some(Status::Approaching));
+        assert_eq!(parse_status("Delayed"), Some(Status::Delayed));
+        assert_eq!(parse_status("Canceled"), Some(Status::Cancelled));
+        assert_eq!(parse_status("Stopped 3 stops away"), None);
+        assert_eq!(parse_status(""), None);
+    }
+
+    #[test]
+    fn test_alert_affects() {
+        let line: TransitLine = serde_json::from_value(json!({
+            "name": "Red",
+            "stops": [{"name": "Park", "id": "place-pktrm"}],
+        }))
+        .unwrap();
+        let affects = |route: Option<&str>, stop: Option<&str>| {
+            let alert = AlertAttributes {
+                effect: "DELAY".into(),
+                header: String::new(),
+                severity: 3,
+                active_period: vec![],
+                informed_entity: vec![InformedEntity {
+                    route: route.map(String::from),
+                    stop: stop.map(String::from),
+                }],
+            };
+            alert.affects(&line)
+        };
+        assert!(affects(Some("Red"), None));
+        assert!(affects(Some("Red"), Some("place-pktrm")));
+        assert!(!affects(Some("Red"), Some("place-dwnxg")));
+        assert!(!affects(Some("Green-B"), None));
+        assert!(!affects(Some("Green-B"), Some("place-pktrm")));
+        // Stop-only entities, e.g. an elevator outage
+        assert!(affects(None, Some("place-pktrm")));
+        assert!(!affects(None, Some("place-dwnxg")));
+        assert!(!affects(None, None));
+    }
+
+    /// Alerts on both a route and a stop come back from both requests
+    #[test]
+    fn test_alerts_merge() {
+        let alerts = |ids: &[&str]| -> ApiAlerts {
+            let data = ids
+                .iter()
+                .map(|id| {
+                    json!({
+                        "id": id,
+                        "attributes": {
+                            "effect": "DELAY",
+                            "header": id,
+                            "severity": 3,
+                            "active_period": [],
+                            "informed_entity": [],
+                        },
+                    })
+                })
+                .collect::<Vec<_>>();
+            serde_json::from_value(json!({"data": data})).unwrap()
+        };
+        let merged = alerts(&["1", "2"]).merge(alerts(&["2", "3"]));
+        let ids: Vec<&str> =
+            merged.data.iter().map(|alert| alert.id.as_str()).collect();
+        assert_eq!(ids, vec!["1", "2", "3"]);
+        assert!(alerts(&[]).merge(alerts(&[])).data.is_empty());
+    }
+
+    /// A prediction or schedule for the Red line at a stop, departing some
+    /// minutes from now. The extra seconds keep the rounding stable while the
+    /// test runs
+    fn api_departure(
+        id: &str,
+        stop: &str,
+        trip: Option<&str>,
+        minutes: Option<i64>,
+    ) -> serde_json::Value {
+        let time = minutes
+            .map(|minutes| Utc::now() + TimeDelta::seconds(minutes * 60 + 20));
+        json!({
+            "id": id,
+            "attributes": {"departure_time": time},
+            "relationships": {
+                "route": {"data": {"id": "Red"}},
+                "stop": {"data": {"id": stop}},
+                "trip": {"data": trip.map(|trip| json!({"id": trip}))},
+            },
+        })
+    }
+
+    /// Each stop covers one case of merging predictions into schedules
+    #[test]
+    fn test_from_response() {
+        let stops = (1..=7)
+            .map(|i| json!({"name": i.to_string(), "id": i.to_string()}))
+            .collect::<Vec<_>>();
+        let line: TransitLine =
+            serde_json::from_value(json!({"name": "Red", "stops": stops}))
+                .unwrap();
+        let with = |mut departure: serde_json::Value,
+                    relationship: &str,
+                    schedule: Option<&str>| {
+            departure["attributes"]["schedule_relationship"] =
+                json!(relationship);
+            if let Some(schedule) = schedule {
+                departure["relationships"]["schedule"] =
+                    json!({"data": {"id": schedule}});
+            }
+            departure
+        };
+        let schedules = json!({"data": [
+            // Replaced by the prediction, which is 2 minutes late
+            api_departure("s-a", "1", Some("a"), Some(10)),
+            api_departure("s-b", "1", Some("b"), Some(20)),
+            // Cancelled
+            api_departure("s-c", "2", Some("c"), Some(10)),
+            // Skipped
+            api_departure("s-d", "3", Some("d"), Some(10)),
+            api_departure("s-e", "3", Some("e"), Some(15)),
+            // No data
+            api_departure("s-f", "4", Some("f"), Some(10)),
+            // The included schedule takes priority
+            api_departure("s-g", "5", Some("g"), Some(10)),
+            // No trip to merge on
+            api_departure("s-j", "7", None, Some(10)),
+        ]});
+        let predictions = json!({
+            "data": [
+                api_departure("p-a", "1", Some("a"), Some(12)),
+                with(
+                    api_departure("p-c", "2", Some("c"), None),
+                    "CANCELLED",
+                    None,
+                ),
+                // Cancelled with no schedule to show
+                with(
+                    api_departure("p-x", "2", Some("x"), None),
+                    "CANCELLED",
+                    None,
+                ),
+                with(
+                    api_departure("p-d", "3", Some("d"), None),
+                    "SKIPPED",
+                    None,
+                ),
+                with(
+                    api_departure("p-f", "4", Some("f"), None),
+                    "NO_DATA",
+                    None,
+                ),
+                with(
+                    api_departure("p-g", "5", Some("g"), Some(12)),
+                    "ADDED",
+                    Some("i-g"),
+                ),
+                // Running late enough to be missing from the schedule request
+                with(
+                    api_departure("p-h", "5", Some("h"), Some(15)),
+                    "ADDED",
+                    Some("i-h"),
+                ),
+                // No schedule at all
+                api_departure("p-i", "6", Some("i"), Some(10)),
+                api_departure("p-j", "7", None, Some(12)),
+            ],
+            "included": [
+                api_departure("i-g", "5", Some("g"), Some(8)),
+                api_departure("i-h", "5", Some("h"), Some(10)),
+            ],
+        });
+        let predictions = TransitPredictions::from_response(
+            &[line],
+            &serde_json::from_value(predictions).unwrap(),
+            &serde_json::from_value(schedules).unwrap(),
+            &ApiVehicles::default(),
+            &HashMap::new(),
+            &ApiAlerts::default(),
+        );
+        // (text, real-time, delay) for each countdown at each stop
+        let stops: Vec<Vec<(String, bool, Option<i64>)>> = predictions.lines[0]
+            .stops
+            .iter()
+            .map(|stop| {
+                stop.predictions
+                    .iter()
+                    .map(|countdown| {
+                        let text = countdown.to_string();
+                        (text, countdown.realtime, countdown.delay())
+                    })
+                    .collect()
+            })
+            .collect();
+        let countdown =
+            |text: &str, realtime, delay| (text.to_owned(), realtime, delay);
+        assert_eq!(
+            stops,
+            vec![
+                vec![
+                    countdown("12m", true, Some(2)),
+                    countdown("20m", false, None),
+                ],
+                vec![countdown("Cancld", true, None)],
+                vec![countdown("15m", false, None)],
+                vec![countdown("10m", false, None)],
+                vec![
+                    countdown("12m", true, Some(4)),
+                    countdown("15m", true, Some(5)),
+                ],
+                vec![countdown("10m", true, None)],
+                vec![
+                    countdown("10m", false, None),
+                    countdown("12m", true, None),
+                ],
+            ]
+        );
+    }
+
+    /// The static schedule only replaces predictions once they're stale
+    #[test]
+    fn test_fallback() {
+        let mut mbta = Mbta::new(&[], Some(gtfs_static::tests::schedule()));
+        assert!(mbta.fallback().is_ok());
+        mbta.updated_at = Some(Instant::now());
+        assert!(mbta.fallback().is_err());
+        mbta.updated_at = Some(Instant::now() - DATA_TTL);
+        assert!(mbta.fallback().is_ok());
+        // Nothing to fall back to
+        assert!(Mbta::new(&[], None).fallback().is_err());
+    }
+}
@@ -0,0 +1,325 @@
+//! Streaming predictions from the MBTA API. Rather than polling, the server
+//! pushes each change to the predictions as a server-sent event, so we get
+//! updates within seconds and only download what changed
+//!
+//! <https://www.mbta.com/developers/v3-api/streaming>
+
+use crate::transit::{
+    DATA_TTL, TransitPredictions,
+    mbta::{ApiPredictions, Mbta, Prediction},
+};
+use serde::{Deserialize, de::DeserializeOwned};
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader},
+    iter,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{error, info, warn};
+
+/// If the stream goes this long without an event, assume the connection is
+/// dead and reconnect. Events come in constantly while service is running,
+/// so this is only hit late at night, where a reconnect doesn't hurt
+const STREAM_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// Reconnect after this long no matter what. ureq's body timeout covers the
+/// whole body rather than the gap between reads, so this is what eventually
+/// unblocks a reader stuck on a silent connection
+const STREAM_MAX_AGE: Duration = Duration::from_secs(30 * 60);
+
+/// Stream predictions until the connection drops, calling `on_update` on each
+/// change. Errors are logged
+pub fn stream_predictions(
+    client: &mut Mbta,
+    on_update: &mut dyn FnMut(TransitPredictions),
+) {
+    let url = client.predictions_url();
+    let Ok(reader) = connect(&url, STREAM_MAX_AGE) else {
+        return;
+    };
+
+    // Read on another thread, so we can keep updating while the stream is
+    // quiet. The reader exits when the stream ends or when it has an event
+    // and we've stopped listening. If we give up on a silent stream, it stays
+    // blocked until the body timeout closes the connection
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for event in events(reader) {
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    // Local copy of the server's predictions and their schedules, keyed by ID
+    let mut table: HashMap<String, Prediction> = HashMap::new();
+    let mut last_event = Instant::now();
+    loop {
+        match rx.recv_timeout(DATA_TTL) {
+            Ok(event) => {
+                last_event = Instant::now();
+                if apply(&mut table, &event).is_err() {
+                    continue;
+                }
+            }
+            // Nothing changed, but countdowns are relative to now so they
+            // still need to be rebuilt
+            Err(RecvTimeoutError::Timeout) => {
+                if last_event.elapsed() >= STREAM_TIMEOUT {
+                    warn!("No events from {url} in {STREAM_TIMEOUT:?}");
+                    break;
+                }
+                // Nothing to count down, or we haven't gotten the initial
+                // reset yet
+                if table.is_empty() {
+                    continue;
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        let (included, data) = table
+            .values()
+            .cloned()
+            .partition(|resource| resource.kind == "schedule");
+        let predictions = ApiPredictions { data, included };
+        client.updated_at = Some(Instant::now());
+        // Piggyback on the stream to keep alerts and schedules fresh
+        client.refresh(&predictions);
+        on_update(client.predictions(&predictions));
+    }
+    info!("Prediction stream for {url} ended");
+}
+
+/// Open an event stream. The body is cut off after `max_age`, even if the
+/// server is still sending
+fn connect(
+    url: &str,
+    max_age: Duration,
+) -> Result<impl BufRead + Send + use<>, ()> {
+    info!("Streaming {url}");
+    let request = ureq::get(url)
+        .header("Accept", "text/event-stream")
+        .config()
+        .timeout_recv_body(Some(max_age))
+        .build();
+    match request.call() {
+        Ok(response) if response.status().is_success() => {
+            Ok(BufReader::new(response.into_body().into_reader()))
+        }
+        Ok(response) => {
+            error!(status = %response.status(), "4xx/5xx response from {url}");
+            Err(())
+        }
+        Err(error) => {
+            error!(%error, "Error streaming {url}");
+            Err(())
+        }
+    }
+}
+
+/// A single server-sent event
+#[derive(Debug)]
+struct Event {
+    /// reset, add, update, or remove
+    name: String,
+    /// JSON payload
+    data: String,
+}
+
+/// Body of a remove event. Only the ID is included
+#[derive(Debug, Deserialize)]
+struct Removed {
+    id: String,
+}
+
+/// Parse a stream of server-sent events. Ends when the stream closes or
+/// errors
+fn events(reader: impl BufRead) -> impl Iterator<Item = Event> {
+    let mut lines = reader.lines();
+    iter::from_fn(move || {
+        let mut name = String::new();
+        let mut data = String::new();
+        loop {
+            let line = match lines.next()? {
+                Ok(line) => line,
+                // Expected once the stream hits its max age
+                Err(error) if is_timeout(&error) => {
+                    info!("Prediction stream timed out");
+                    return None;
+                }
+                Err(error) => {
+                    error!(%error, "Error reading prediction stream");
+                    return None;
+                }
+            };
+            // A blank line ends the event
+            if line.is_empty() {
+                if !data.is_empty() {
+                    return Some(Event { name, data });
+                }
+                continue;
+            }
+            // Comments (keep-alives) and unknown fields are ignored
+            if let Some(value) = line.strip_prefix("event:") {
+                name = value.trim().to_owned();
+            } else if let Some(value) = line.strip_prefix("data:") {
+                if !data.is_empty() {
+                    data.push('\n');
+                }
+                data.push_str(value.trim_start());
+            }
+        }
+    })
+}
+
+/// Did ureq give up on reading the body?
+fn is_timeout(error: &io::Error) -> bool {
+    error
+        .get_ref()
+        .and_then(|error| error.downcast_ref::<ureq::Error>())
+        .is_some_and(|error| matches!(error, ureq::Error::Timeout(_)))
+}
+
+/// Update the prediction table from an event
+fn apply(
+    table: &mut HashMap<String, Prediction>,
+    event: &Event,
+) -> Result<(), ()> {
+    match event.name.as_str() {
+        "reset" => {
+            let predictions: Vec<Prediction> = parse(event)?;
+            *table = predictions
+                .into_iter()
+                .map(|prediction| (prediction.id.clone(), prediction))
+                .collect();
+        }
+        "add" | "update" => {
+            let prediction: Prediction = parse(event)?;
+            table.insert(prediction.id.clone(), prediction);
+        }
+        "remove" => {
+            let removed: Removed = parse(event)?;
+            table.remove(&removed.id);
+        }
+        name => {
+            error!("Unknown event `{name}` in prediction stream");
+            return Err(());
+        }
+    }
+    Ok(())
+}
+
+fn parse<T: DeserializeOwned>(event: &Event) -> Result<T, ()> {
+    serde_json::from_str(&event.data).map_err(|error| {
+        error!(%error, event = event.name, "Invalid prediction stream data")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::{io::Write, net::TcpListener};
+
+    fn prediction(id: &str, minute: u32) -> String {
+        json!({
+            "id": id,
+            "type": "prediction",
+            "attributes": {
+                "departure_time": format!("2025-06-01T12:{minute:02}:00Z"),
+            },
+            "relationships": {
+                "route": {"data": {"id": "86"}},
+                "stop": {"data": {"id": "2374"}},
+                "trip": {"data": {"id": format!("trip-{id}")}},
+            },
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_events() {
+        let stream = format!(
+            ": keep-alive\n\n\
+            event: reset\ndata: [{}]\n\n\
+            event: remove\ndata: {{\"id\":\n\
+            data: \"1\"}}\n\
+            \n\
+            event: update\n\
+            data: {}",
+            prediction("1", 5),
+            prediction("2", 10),
+        );
+        let events: Vec<Event> = events(stream.as_bytes()).collect();
+        let names: Vec<&str> =
+            events.iter().map(|event| event.name.as_str()).collect();
+        // Comments are skipped, and the last event is cut off before its
+        // blank line
+        assert_eq!(names, ["reset", "remove"]);
+        // Multiple data lines are joined with newlines
+        assert_eq!(events[1].data, "{\"id\":\n\"1\"}");
+    }
+
+    #[test]
+    fn test_apply() {
+        let event = |name: &str, data: String| Event {
+            name: name.into(),
+            data,
+        };
+        let mut table = HashMap::new();
+        let reset = format!("[{},{}]", prediction("1", 5), prediction("2", 10));
+        apply(&mut table, &event("reset", reset)).unwrap();
+        assert_eq!(table.len(), 2);
+
+        apply(&mut table, &event("add", prediction("3", 15))).unwrap();
+        apply(&mut table, &event("update", prediction("1", 7))).unwrap();
+        let departure = table["1"].attributes.departure_time.unwrap();
+        assert_eq!(departure.to_rfc3339(), "2025-06-01T12:07:00+00:00");
+
+        let remove = json!({"id": "2", "type": "prediction"}).to_string();
+        apply(&mut table, &event("remove", remove)).unwrap();
+        let mut ids: Vec<&str> = table.keys().map(String::as_str).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, ["1", "3"]);
+
+        // Bad events leave the table alone
+        assert!(apply(&mut table, &event("update", "{}".into())).is_err());
+        assert!(apply(&mut table, &event("bogus", "{}".into())).is_err());
+        assert_eq!(table.len(), 2);
+
+        let reset = format!("[{}]", prediction("4", 20));
+        apply(&mut table, &event("reset", reset)).unwrap();
+        assert_eq!(table.keys().collect::<Vec<_>>(), ["4"]);
+    }
+
+    /// A server that sends one event and then goes quiet without closing the
+    /// connection. The reader should still give up
+    #[test]
+    fn test_silent_stream() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = BufReader::new(&stream);
+            let mut line = String::new();
+            while request.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\r\n\
+                event: reset\ndata: []\n\n"
+            )
+            .unwrap();
+            thread::sleep(Duration::from_secs(60));
+        });
+
+        let start = Instant::now();
+        let reader = connect(&url, Duration::from_millis(500)).unwrap();
+        let names: Vec<String> =
+            events(reader).map(|event| event.name).collect();
+        assert_eq!(names, vec!["reset"]);
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+}
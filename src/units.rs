@@ -0,0 +1,131 @@
+use serde::Deserialize;
+use std::fmt::{self, Display};
+
+/// System of measurement to fetch and display data in
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Units {
+    /// Fahrenheit, miles, inches
+    #[default]
+    Imperial,
+    /// Celsius, kilometers, millimeters
+    Metric,
+}
+
+impl Units {
+    /// Unit used for temperatures in this system
+    pub fn temperature(self) -> TemperatureUnit {
+        match self {
+            Self::Imperial => TemperatureUnit::Fahrenheit,
+            Self::Metric => TemperatureUnit::Celsius,
+        }
+    }
+
+    /// Unit used for precipitation amounts in this system
+    pub fn precipitation(self) -> PrecipitationUnit {
+        match self {
+            Self::Imperial => PrecipitationUnit::Inches,
+            Self::Metric => PrecipitationUnit::Millimeters,
+        }
+    }
+
+    /// Unit used for wind speeds in this system
+    pub fn speed(self) -> SpeedUnit {
+        match self {
+            Self::Imperial => SpeedUnit::MilesPerHour,
+            Self::Metric => SpeedUnit::KilometersPerHour,
+        }
+    }
+}
+
+/// Unit for a temperature value. Deserializes from the single-letter codes
+/// used by NWS
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+pub enum TemperatureUnit {
+    #[default]
+    #[serde(rename = "F")]
+    Fahrenheit,
+    #[serde(rename = "C")]
+    Celsius,
+}
+
+impl TemperatureUnit {
+    /// Convert a temperature in this unit to Fahrenheit
+    pub fn to_fahrenheit(self, value: f64) -> f64 {
+        match self {
+            Self::Fahrenheit => value,
+            Self::Celsius => value * 9.0 / 5.0 + 32.0,
+        }
+    }
+
+    /// Convert a temperature in Fahrenheit to this unit
+    pub fn convert_from_fahrenheit(self, value: f64) -> f64 {
+        match self {
+            Self::Fahrenheit => value,
+            Self::Celsius => (value - 32.0) * 5.0 / 9.0,
+        }
+    }
+}
+
+impl Display for TemperatureUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fahrenheit => write!(f, "°F"),
+            Self::Celsius => write!(f, "°C"),
+        }
+    }
+}
+
+/// Unit for a liquid precipitation amount
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum PrecipitationUnit {
+    #[default]
+    Inches,
+    Millimeters,
+}
+
+impl PrecipitationUnit {
+    /// Format an amount in this unit, with an appropriate precision
+    pub fn format(self, amount: f64) -> String {
+        match self {
+            Self::Inches => format!("{amount:.2}{self}"),
+            Self::Millimeters => format!("{amount:.0}{self}"),
+        }
+    }
+}
+
+impl Display for PrecipitationUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Inches => write!(f, "in"),
+            Self::Millimeters => write!(f, "mm"),
+        }
+    }
+}
+
+/// Unit for a wind speed
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum SpeedUnit {
+    #[default]
+    MilesPerHour,
+    KilometersPerHour,
+}
+
+impl SpeedUnit {
+    /// Convert a speed in km/h to this unit
+    pub fn convert_from_kilometers_per_hour(self, value: f64) -> f64 {
+        match self {
+            Self::MilesPerHour => value / 1.609344,
+            Self::KilometersPerHour => value,
+        }
+    }
+}
+
+impl Display for SpeedUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MilesPerHour => write!(f, "mph"),
+            Self::KilometersPerHour => write!(f, "km/h"),
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use crate::{config::Config, state::Tx};
 use serde::de::DeserializeOwned;
-use std::thread;
+use std::{
+    fmt::{self, Display},
+    thread,
+};
 use tracing::{error, info};
 
 /// Spawn a background thread with access to the message channel
@@ -14,10 +17,15 @@ pub fn spawn(
     thread::spawn(move || f(config, tx));
 }
 
+/// Query parameters whose values are secret, and shouldn't end up in the log.
+/// Compared case-insensitively
+const SECRET_PARAMS: [&str; 4] = ["api_key", "apikey", "key", "token"];
+
 /// Make an HTTP GET request
 pub fn http_get<T: DeserializeOwned>(url: &str) -> Result<T, ()> {
+    let url = &Url(url);
     info!("Fetching {url}");
-    match ureq::get(url).call() {
+    match ureq::get(url.0).call() {
         Ok(mut response) if response.status().is_success() => {
             let data: T = response.body_mut().read_json().unwrap();
             Ok(data)
@@ -36,9 +44,126 @@ pub fn http_get<T: DeserializeOwned>(url: &str) -> Result<T, ()> {
     }
 }
 
+/// Make an HTTP GET request for a binary body, e.g. a protobuf feed
+pub fn http_get_bytes(url: &str) -> Result<Vec<u8>, ()> {
+    /// Some agencies' feeds are big. Default limit is 10MB
+    const MAX_SIZE: u64 = 50 * 1024 * 1024;
+
+    let url = &Url(url);
+    info!("Fetching {url}");
+    match ureq::get(url.0).call() {
+        Ok(mut response) if response.status().is_success() => response
+            .body_mut()
+            .with_config()
+            .limit(MAX_SIZE)
+            .read_to_vec()
+            .map_err(|error| error!(%error, "Error reading body from {url}")),
+        Ok(response) => {
+            error!(
+                status = %response.status(),
+                "4xx/5xx response from {url}"
+            );
+            Err(())
+        }
+        Err(error) => {
+            error!(%error, "Error fetching {url}");
+            Err(())
+        }
+    }
+}
+
+/// A URL that displays with secret query parameters redacted, for logging
+struct Url<'a>(&'a str);
+
+impl Display for Url<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some((base, query)) = self.0.split_once('?') else {
+            return write!(f, "{}", self.0);
+        };
+        write!(f, "{base}?")?;
+        for (i, param) in query.split('&').enumerate() {
+            if i > 0 {
+                write!(f, "&")?;
+            }
+            match param.split_once('=') {
+                Some((name, _))
+                    if SECRET_PARAMS
+                        .iter()
+                        .any(|secret| name.eq_ignore_ascii_case(secret)) =>
+                {
+                    write!(f, "{name}=REDACTED")?
+                }
+                _ => write!(f, "{param}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
 /// TODO
 pub fn scale_to(value: f64, from: (f64, f64), to: (f64, f64)) -> f64 {
     let from_span = from.1 - from.0;
     let to_span = to.1 - to.0;
     (value - (from.0)) / from_span * to_span + to.0
 }
+
+/// A tiny HTTP server for testing API clients against canned responses
+#[cfg(test)]
+pub mod stub_server {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    /// Serve `(path prefix, status, body)` responses on a random local port,
+    /// forever. Unmatched paths get a 404. Returns the host to point a client
+    /// at
+    pub fn serve(routes: Vec<(&'static str, u16, String)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                // e.g. "GET /path?query HTTP/1.1". Skip the headers
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let path = request_line.split(' ').nth(1).unwrap_or_default();
+                let (status, body) = routes
+                    .iter()
+                    .find(|(prefix, _, _)| path.starts_with(prefix))
+                    .map(|(_, status, body)| (*status, body.as_str()))
+                    .unwrap_or((404, ""));
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} Stub\r\nContent-Type: application/json\
+                    \r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+        host
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_url() {
+        let url = "https://example.com/data?format=json&API_KEY=abc123&x=1";
+        assert_eq!(
+            Url(url).to_string(),
+            "https://example.com/data?format=json&API_KEY=REDACTED&x=1"
+        );
+        let url = "https://example.com/data";
+        assert_eq!(Url(url).to_string(), url);
+    }
+}
@@ -1,113 +1,551 @@
 use crate::{
     State,
+    air_quality::{AirQuality, AqiReading},
+    bike_share::BikeShare,
+    config::{Coordinates, IconSet, PrecipitationDisplay},
+    moon::MoonPhase,
     state::Mode,
-    transit::{LinePredictions, StopPredictions, TransitPredictions},
+    sun::{self, Daylight},
+    transit::{
+        Alert, Crowding, Lateness, LinePredictions, StopPredictions,
+        TransitPredictions,
+    },
+    units::TemperatureUnit,
     util::scale_to,
-    weather::WeatherForecast,
+    weather::{Condition, ForecastPeriod, Warning, WeatherForecast},
 };
+use chrono::{DateTime, Local, TimeDelta, Timelike, Utc};
 use itertools::{Itertools, MinMaxResult};
 use ratatui::{
     Frame,
     buffer::Buffer,
-    layout::{Alignment, Constraint, Layout, Rect, Size},
+    layout::{Constraint, Layout, Rect, Size},
     style::{Color, Modifier, Style},
     symbols,
-    text::{Line, Text},
-    widgets::{Axis, Chart, Dataset, GraphType, Tabs, Widget},
+    text::{Line, Span, Text},
+    widgets::{
+        Axis, Block, Borders, Chart, Dataset, GraphType, Paragraph, Sparkline,
+        Tabs, Widget, Wrap,
+    },
 };
-use std::{iter, sync::LazyLock};
+use std::{iter, ops::Range, sync::LazyLock};
 
 /// Display width
 pub const DIMENSIONS: Size = Size {
     width: 24,
     height: 12,
 };
+/// First row of the content for each mode
+const CONTENT_ROW: u16 = 2;
+/// How many ticks each page of transit lines is shown for
+const TICKS_PER_TRANSIT_PAGE: usize = 2;
 /// Styles are statically defined, so we only need one copy
 static STYLES: LazyLock<Styles> = LazyLock::new(Styles::default);
 
 /// Draw to the terminal
 pub fn draw(frame: &mut Frame, state: &State) {
-    let [mode_area, _, content_area] = Layout::vertical([
+    let [mode_area, header_area, content_area] = Layout::vertical([
         Constraint::Length(1),
         Constraint::Length(1),
         Constraint::Min(0),
     ])
     .areas(frame.area());
 
+    // Abbreviate all the tab names if they don't fit, with a divider between
+    // each
+    let modes = Mode::enabled(&state.config);
+    let mut tabs: Vec<String> = modes.iter().map(Mode::to_string).collect();
+    let width = tabs.iter().map(String::len).sum::<usize>() + tabs.len() - 1;
+    if width > usize::from(mode_area.width) {
+        tabs = modes.iter().map(|mode| mode.short_name().into()).collect();
+    }
     frame.render_widget(
-        Tabs::new(Mode::ALL.iter().map(Mode::to_string))
-            .select(index_of(&Mode::ALL, state.mode))
-            .highlight_style(STYLES.tab_highlight),
+        Tabs::new(tabs)
+            .select(index_of(&modes, state.mode))
+            .highlight_style(STYLES.tab_highlight)
+            // Padding doesn't fit on the tiny screen
+            .padding("", ""),
         mode_area,
     );
 
+    // Only bother with a location header if there's somewhere else to go
+    if state.mode.has_locations() && state.weather.len() > 1 {
+        frame.render_widget(location_line(state), header_area);
+    }
     match state.mode {
-        Mode::Transit => frame.render_widget(&state.transit, content_area),
-        Mode::Weather => frame.render_widget(&state.weather, content_area),
+        Mode::Transit => match state
+            .expanded_alert
+            .and_then(|index| state.transit.lines.get(index))
+        {
+            Some(line) => frame.render_widget(alert_detail(line), content_area),
+            None => {
+                let pages = transit_pages(&state.transit);
+                let page = transit_page(state, pages.len());
+                if pages.len() > 1 {
+                    frame.render_widget(
+                        Line::from(format!("{}/{}", page + 1, pages.len()))
+                            .style(STYLES.page_indicator)
+                            .right_aligned(),
+                        header_area,
+                    );
+                }
+                frame.render_widget(
+                    TransitPage {
+                        lines: &state.transit.lines[pages[page].clone()],
+                    },
+                    content_area,
+                );
+            }
+        },
+        Mode::Weather => draw_weather(frame, state, content_area),
+        Mode::AirQuality => {
+            frame.render_widget(&state.air_quality, content_area)
+        }
+        Mode::BikeShare => frame.render_widget(&state.bike_share, content_area),
+        Mode::History => frame.render_widget(
+            HistoryChart {
+                temperatures: state.history.temperatures(
+                    state.location_name(),
+                    Utc::now() - TimeDelta::days(HistoryChart::DAYS),
+                    state.config.units.temperature(),
+                ),
+                unit: state.config.units.temperature(),
+            },
+            content_area,
+        ),
     }
 }
 
-impl Widget for &TransitPredictions {
+/// Draw the hourly forecast chart, with a summary of the day below it. The
+/// precipitation outlook is always shown. There's only room for one more line,
+/// so the rest take turns
+fn draw_weather(frame: &mut Frame, state: &State, area: Rect) {
+    let forecast = state.weather();
+    let warnings = state.config.weather_warnings.evaluate(forecast);
+    // Warnings steal a row from the chart, only when there are any
+    let [chart_area, warning_area, precipitation_area, rotating_area] =
+        Layout::vertical([
+            Constraint::Min(0),
+            Constraint::Length(warnings.len().min(1) as u16),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+    let coordinates = state.config.locations()[state.location].coordinates;
+    frame.render_widget(
+        WeatherChart {
+            forecast,
+            coordinates,
+            precipitation_display: state.config.precipitation_display,
+            icons: state.config.weather_icons,
+        },
+        chart_area,
+    );
+    frame.render_widget(
+        precipitation_line(forecast, state.config.precipitation_threshold)
+            .centered(),
+        precipitation_area,
+    );
+    // Cycle through warnings if there's more than one
+    if let Some(warning) = warnings.get(state.ticks % warnings.len().max(1)) {
+        frame.render_widget(warning_line(warning).centered(), warning_area);
+    }
+    let rotating_lines: Vec<Line> = [
+        coordinates.map(|coordinates| outside_line(forecast, coordinates)),
+        yesterday_line(state),
+        coordinates.map(sun_line),
+        Some(moon_line()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let rotating_line = &rotating_lines[state.ticks % rotating_lines.len()];
+    frame.render_widget(rotating_line.clone().centered(), rotating_area);
+}
+
+/// Name of the weather location being displayed, with arrows to hint that
+/// you can swipe between them
+fn location_line(state: &State) -> Line<'static> {
+    let name = state.location_name();
+    Line::from(format!(
+        "‹ {name} {}/{} ›",
+        state.location + 1,
+        state.weather.len()
+    ))
+    .style(STYLES.weather_location)
+    .centered()
+}
+
+/// Transit lines that fit on one screen
+struct TransitPage<'a> {
+    lines: &'a [LinePredictions],
+}
+
+impl Widget for TransitPage<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
         /// Convert a transit line into a text line
         fn line_to_lines(
             line: &LinePredictions,
+            width: u16,
+        ) -> impl Iterator<Item = Line<'_>> {
+            // One row for the line label, then another row for each stop.
+            // If there are alerts, flag the most severe one next to the name
+            let mut label =
+                Line::from(line.name.as_str()).style(STYLES.transit_line_name);
+            if let Some(alert) = line.alerts.first() {
+                let more = match line.alerts.len() {
+                    1 => String::new(),
+                    n => format!(" +{}", n - 1),
+                };
+                label.push_span(
+                    Span::from(format!(" ⚠ {}{more}", alert.effect))
+                        .style(alert_style(alert)),
+                );
+            }
+            iter::once(label)
+                .chain(
+                    line.stops
+                        .iter()
+                        .flat_map(move |stop| stop_to_lines(stop, width)),
+                )
+                .chain(iter::once("".into())) // Blank line between
+        }
+
+        /// Real-time departures are colored by how late they are, and
+        /// scheduled-only times are dimmed, since they aren't tracked live.
+        /// The next departure gets a crowding indicator, and is highlighted
+        /// if the stop has a walking time. Delays are spelled out if there's
+        /// room
+        fn stop_to_lines(
+            stop: &StopPredictions,
+            width: u16,
         ) -> impl Iterator<Item = Line<'_>> {
-            // One row for the line label, then another row for each stop
-            iter::once(
-                Line::from(line.name.as_str()).style(STYLES.transit_line_name),
-            )
-            .chain(line.stops.iter().map(stop_to_line))
-            .chain(iter::once("".into())) // Blank line between
+            let mut line = departures_line(stop, true);
+            if line.width() > usize::from(width) {
+                line = departures_line(stop, false);
+            }
+            iter::once(line).chain(stop_detail(stop))
         }
 
-        fn stop_to_line(stop: &StopPredictions) -> Line<'_> {
-            Line::from(format!("{:>7} {}", stop.name, stop.predictions))
+        fn departures_line(
+            stop: &StopPredictions,
+            show_delay: bool,
+        ) -> Line<'static> {
+            let mut line = Line::from(format!("{:>7} ", stop.name));
+            if stop.predictions.is_empty() {
+                line.push_span("None");
+            }
+            // The first departure that's actually running
+            let mut is_next = true;
+            for (i, countdown) in stop.predictions.iter().enumerate() {
+                if i > 0 {
+                    line.push_span(", ");
+                }
+                let mut style = match countdown.lateness() {
+                    Some(Lateness::OnTime) => STYLES.transit_on_time,
+                    Some(Lateness::Late) => STYLES.transit_late,
+                    Some(Lateness::VeryLate) => STYLES.transit_very_late,
+                    None if countdown.is_cancelled() => {
+                        STYLES.transit_cancelled
+                    }
+                    None if countdown.is_realtime() => Style::default(),
+                    None => STYLES.transit_scheduled,
+                };
+                let next = is_next && !countdown.is_cancelled();
+                if next && stop.leave_in.is_some() {
+                    style = style.patch(STYLES.transit_leave_for);
+                }
+                let mut text = countdown.to_string();
+                // Show how late it is, if it isn't on time
+                if show_delay
+                    && let Some(delay) = countdown.delay()
+                    && countdown.lateness() != Some(Lateness::OnTime)
+                {
+                    text += &format!(" (+{delay})");
+                }
+                line.push_span(Span::from(text).style(style));
+                if next {
+                    if let Some(crowding) =
+                        countdown.vehicle().and_then(|vehicle| vehicle.crowding)
+                    {
+                        line.push_span(crowding_span(crowding));
+                    }
+                    is_next = false;
+                }
+            }
+            line
         }
 
-        let text: Text = self.lines.iter().flat_map(line_to_lines).collect();
+        let text: Text = self
+            .lines
+            .iter()
+            .flat_map(|line| line_to_lines(line, area.width))
+            .collect();
         text.render(area, buf);
     }
 }
 
-impl Widget for &WeatherForecast {
+/// Find which transit line's label is on a screen row, if any. This mirrors
+/// the layout of the transit view
+pub fn transit_line_at(state: &State, row: u16) -> Option<usize> {
+    let pages = transit_pages(&state.transit);
+    let mut line_row = CONTENT_ROW;
+    for index in pages[transit_page(state, pages.len())].clone() {
+        if row == line_row {
+            return Some(index);
+        }
+        line_row += transit_line_rows(&state.transit.lines[index]);
+    }
+    None
+}
+
+/// Split transit lines into pages that fit on the screen. Lines are never
+/// split across pages; if one line is too tall on its own, it gets cut off
+fn transit_pages(transit: &TransitPredictions) -> Vec<Range<usize>> {
+    let height = DIMENSIONS.height - CONTENT_ROW;
+    let mut pages = Vec::new();
+    let mut start = 0;
+    let mut used = 0;
+    for (index, line) in transit.lines.iter().enumerate() {
+        let rows = transit_line_rows(line);
+        // The trailing blank row is allowed to fall off the bottom
+        if index > start && used + rows - 1 > height {
+            pages.push(start..index);
+            start = index;
+            used = 0;
+        }
+        used += rows;
+    }
+    pages.push(start..transit.lines.len());
+    pages
+}
+
+/// Index of the transit page to show. Pages rotate automatically, since
+/// there's no room for scroll controls
+fn transit_page(state: &State, pages: usize) -> usize {
+    (state.ticks / TICKS_PER_TRANSIT_PAGE) % pages
+}
+
+/// Number of rows a transit line takes up: label, stops (plus detail rows),
+/// and a blank line
+fn transit_line_rows(line: &LinePredictions) -> u16 {
+    let stop_rows: usize = line
+        .stops
+        .iter()
+        .map(|stop| 1 + usize::from(stop_detail(stop).is_some()))
+        .sum();
+    stop_rows as u16 + 2
+}
+
+/// Extra row under a stop, with when to leave and where the next vehicle is.
+/// `None` if there's nothing to show
+fn stop_detail(stop: &StopPredictions) -> Option<Line<'static>> {
+    let stops_away = stop
+        .predictions
+        .next()
+        .and_then(|countdown| countdown.vehicle()?.stops_away)
+        // Arrival status already covers a vehicle that's at the stop
+        .filter(|stops_away| *stops_away > 0);
+    let leave = stop.leave_in.map(|minutes| match minutes {
+        ..=0 => "leave now".to_owned(),
+        _ => format!("leave in {minutes}m"),
+    });
+    let away = stops_away.map(|stops_away| {
+        let plural = if stops_away == 1 { "" } else { "s" };
+        // Shortened to fit next to the leave countdown
+        let suffix = if leave.is_some() { "" } else { " away" };
+        format!("{stops_away} stop{plural}{suffix}")
+    });
+    if leave.is_none() && away.is_none() {
+        return None;
+    }
+    let mut line = Line::default().right_aligned();
+    if let Some(leave) = leave {
+        line.push_span(Span::from(leave).style(STYLES.transit_leave));
+    }
+    if let Some(away) = away {
+        if !line.spans.is_empty() {
+            line.push_span(" · ");
+        }
+        line.push_span(away);
+    }
+    Some(line)
+}
+
+/// Small bar showing how full a vehicle is
+fn crowding_span(crowding: Crowding) -> Span<'static> {
+    let (symbol, style) = match crowding {
+        Crowding::NotCrowded => ("▂", STYLES.transit_crowding[0]),
+        Crowding::SomewhatCrowded => ("▅", STYLES.transit_crowding[1]),
+        Crowding::Crowded => ("█", STYLES.transit_crowding[2]),
+    };
+    Span::from(symbol).style(style)
+}
+
+/// Full text of all alerts on a transit line. Shown in place of the transit
+/// view when the line is tapped
+fn alert_detail(line: &LinePredictions) -> Paragraph<'_> {
+    let lines = iter::once(
+        Line::from(line.name.as_str()).style(STYLES.transit_line_name),
+    )
+    .chain(line.alerts.iter().flat_map(|alert| {
+        [
+            Line::from(format!("⚠ {}", alert.effect)).style(alert_style(alert)),
+            Line::from(alert.header.as_str()),
+        ]
+    }))
+    .collect::<Vec<_>>();
+    Paragraph::new(lines).wrap(Wrap { trim: true })
+}
+
+fn alert_style(alert: &Alert) -> Style {
+    if alert.severity >= Alert::SEVERE {
+        STYLES.transit_alert_severe
+    } else {
+        STYLES.transit_alert
+    }
+}
+
+impl Widget for &AirQuality {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        fn reading_line(reading: &AqiReading) -> Line<'_> {
+            let aqi = reading
+                .aqi
+                .map(|aqi| aqi.to_string())
+                .unwrap_or_else(|| "--".into());
+            Line::from(vec![
+                Span::from(format!("AQI {aqi:>3} ")),
+                Span::styled(
+                    reading.category.to_string(),
+                    STYLES.aqi_categories[reading.category as usize],
+                ),
+            ])
+        }
+
+        let mut lines: Vec<Line> = Vec::new();
+        match &self.current {
+            Some(current) => {
+                lines.push(reading_line(current));
+                lines.push(Line::from(format!("Main: {}", current.pollutant)));
+            }
+            None => lines.push(Line::from("No data")),
+        }
+        if let Some(tomorrow) = &self.tomorrow {
+            lines.push(Line::default());
+            lines.push(Line::from("Tomorrow").style(STYLES.section_header));
+            lines.push(reading_line(tomorrow));
+        }
+        if !self.pollen.is_empty() {
+            lines.push(Line::default());
+            lines.push(Line::from("Pollen").style(STYLES.section_header));
+            lines.extend(self.pollen.iter().map(|pollen| {
+                Line::from(format!(
+                    "{:<8} {:>5.0}/m³",
+                    pollen.name, pollen.grains
+                ))
+            }));
+        }
+        Text::from(lines).render(area, buf);
+    }
+}
+
+impl Widget for &BikeShare {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        /// Counts at or below this are running low
+        const LOW: u32 = 2;
+
+        /// Color a count by how many are left
+        fn count_span(count: u32, width: usize) -> Span<'static> {
+            let style = match count {
+                0 => STYLES.bike_empty,
+                1..=LOW => STYLES.bike_low,
+                _ => Style::default(),
+            };
+            Span::styled(format!(" {count:>width$}"), style)
+        }
+
+        if self.stations.is_empty() {
+            Line::from("No data").render(area, buf);
+            return;
+        }
+        // Only show an e-bike column if the system reports them
+        let has_ebikes = self.stations.iter().any(|station| {
+            station
+                .availability
+                .is_some_and(|availability| availability.ebikes.is_some())
+        });
+        let header = if has_ebikes {
+            format!("{:>7} Bike Ebike Dock", "")
+        } else {
+            format!("{:>7} Bike Dock", "")
+        };
+        let lines = iter::once(Line::from(header).style(STYLES.section_header))
+            .chain(self.stations.iter().map(|station| {
+                let mut line = Line::from(format!("{:>7.7}", station.name));
+                match station.availability {
+                    Some(availability) if availability.open => {
+                        line.push_span(count_span(availability.bikes, 4));
+                        if has_ebikes {
+                            line.push_span(count_span(
+                                availability.ebikes.unwrap_or(0),
+                                5,
+                            ));
+                        }
+                        // Dockless systems might not have a count
+                        line.push_span(match availability.docks {
+                            Some(docks) => count_span(docks, 4),
+                            None => Span::from(format!(" {:>4}", "-")),
+                        });
+                    }
+                    Some(_) => line.push_span(
+                        Span::from(" Closed").style(STYLES.bike_closed),
+                    ),
+                    None => line.push_span(" None"),
+                }
+                line
+            }))
+            .collect::<Vec<_>>();
+        Text::from(lines).render(area, buf);
+    }
+}
+
+/// Hourly forecast chart, with day/night shading for a location. Without
+/// coordinates there's no shading, and icons are all daytime
+struct WeatherChart<'a> {
+    forecast: &'a WeatherForecast,
+    coordinates: Option<Coordinates>,
+    precipitation_display: PrecipitationDisplay,
+    icons: IconSet,
+}
+
+impl Widget for WeatherChart<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
         const PERIODS: usize = 25;
+        /// Height of the precipitation probability bars, if there's room
+        const BARS_HEIGHT: u16 = 2;
+        /// Rows the temperature plot needs to be readable, including the
+        /// x axis. Extra rows are given up to make room for it
+        const MIN_PLOT_HEIGHT: u16 = 3;
 
-        let (labels, temps, precips): (Vec<_>, Vec<_>, Vec<_>) = self
-            .periods()
-            .take(PERIODS)
+        let periods: Vec<&ForecastPeriod> =
+            self.forecast.periods().take(PERIODS).collect();
+        let (times, temps, precips): (Vec<_>, Vec<_>, Vec<_>) = periods
+            .iter()
             .map(|period| {
                 let time = period.start_time();
-                let x = period.start_time().timestamp() as f64;
-                let label = {
-                    let mut label = time.format("%-I%P").to_string();
-                    label.pop(); // Remove the 'm' from 'am'/'pm'
-                    label
-                };
-                let temp = (x, period.temp() as f64);
-                let precip = (x, period.pop() as f64);
-                (label, temp, precip)
+                let x = time.timestamp() as f64;
+                let temp = (x, period.temp());
+                let precip = (x, period.pop());
+                (time, temp, precip)
             })
             .multiunzip();
-
-        // Calculate x/y bounds based on the temperature data. Both lines will
-        // have the same x values. Bound the y based on temperature, so it zooms
-        // in as much as possible. The precip line will still be 0-100, but
-        // without labels
-        let (min_x, max_x, min_temp, max_temp) = if temps.is_empty() {
-            (0.0, 0.0, 0.0, 0.0)
-        } else {
-            let min_x = temps.first().unwrap().0;
-            let max_x = temps.last().unwrap().0;
-            let (min_temp, max_temp) =
-                match temps.iter().map(|(_, temp)| *temp).minmax() {
-                    MinMaxResult::NoElements => (0.0, 0.0),
-                    MinMaxResult::OneElement(value) => (value, value),
-                    MinMaxResult::MinMax(min, max) => (min, max),
-                };
-            (min_x, max_x, min_temp, max_temp)
+        let chart = TemperatureChart {
+            temps: &temps,
+            unit: self.forecast.temperature_unit(),
+            times: &times,
         };
+        let [min_temp, max_temp] = chart.y_bounds();
+        let [min_x, max_x] = chart.x_bounds();
 
         // Scale the precip values to be in the temperature y range. This will
         // make the dots visually equivalent to being on their own 0-100 scale
@@ -119,38 +557,839 @@ impl Widget for &WeatherForecast {
             })
             .collect();
 
-        // Build the lines from temp/precip data
-        let datasets = vec![
-            Dataset::default()
-                .name("precip")
-                .marker(symbols::Marker::Braille)
-                .graph_type(GraphType::Line)
+        // Vertical marker for the current time. This goes first so the data
+        // lines are drawn on top of it
+        let now = Utc::now().timestamp() as f64;
+        let now_marker = [(now, min_temp), (now, max_temp)];
+        let mut datasets = Vec::new();
+        if (min_x..=max_x).contains(&now) {
+            datasets.push(
+                Dataset::default()
+                    .marker(symbols::Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(STYLES.weather_now_marker)
+                    .data(&now_marker),
+            );
+        }
+        // In bar mode, precip gets its own rows below the chart instead
+        if self.precipitation_display == PrecipitationDisplay::Line {
+            datasets.push(
+                Dataset::default()
+                    .name("precip")
+                    .marker(symbols::Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(STYLES.weather_line_precipitation)
+                    .data(&precips),
+            );
+        }
+
+        // Bars get a row for probability, plus one for amount if the provider
+        // gives us amounts and any are forecasted
+        let max_amount = periods
+            .iter()
+            .filter_map(|period| period.precipitation_amount())
+            .max_by(f64::total_cmp)
+            .filter(|amount| *amount > 0.0);
+        let (mut bars_height, mut amount_height) =
+            match self.precipitation_display {
+                PrecipitationDisplay::Line => (0, 0),
+                PrecipitationDisplay::Bars => {
+                    (BARS_HEIGHT, u16::from(max_amount.is_some()))
+                }
+            };
+        let mut uv_height =
+            u16::from(periods.iter().any(|p| p.uv_index().is_some()));
+
+        // Icons and x labels always get a row. If the rest doesn't leave
+        // enough for the plot (e.g. when a warning takes a row), give up the
+        // amounts, then half the bars, then UV
+        let available = area.height.saturating_sub(2 + MIN_PLOT_HEIGHT);
+        let extra_height = |bars, amount, uv| bars + amount + uv;
+        if extra_height(bars_height, amount_height, uv_height) > available {
+            amount_height = 0;
+        }
+        if extra_height(bars_height, amount_height, uv_height) > available {
+            bars_height = bars_height.min(1);
+        }
+        if extra_height(bars_height, amount_height, uv_height) > available {
+            uv_height = 0;
+        }
+
+        // Bars and amounts are labeled in the same column as temperatures
+        let mut labels = Vec::new();
+        match bars_height {
+            0 => {}
+            // Not enough room for a scale
+            1 => labels.push(Line::from("%")),
+            _ => labels.extend([Line::from("100%"), Line::from("0%")]),
+        }
+        if let (Some(max_amount), 1) = (max_amount, amount_height) {
+            let unit = periods[0].precipitation_unit();
+            labels.push(Line::from(unit.format(max_amount)));
+        }
+        let label_width = chart
+            .labels()
+            .iter()
+            .chain(&labels)
+            .map(Line::width)
+            .max()
+            .unwrap_or_default() as u16;
+        let [
+            icon_area,
+            uv_area,
+            plot_area,
+            bars_area,
+            amount_area,
+            x_label_area,
+        ] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(uv_height),
+            Constraint::Min(0),
+            Constraint::Length(bars_height),
+            Constraint::Length(amount_height),
+            Constraint::Length(1),
+        ])
+        .areas(area);
+        // Without geometry there's nothing to line the other rows up with
+        let Some((label_area, geometry)) =
+            chart.render(buf, plot_area, x_label_area, label_width, datasets)
+        else {
+            return;
+        };
+        let graph_area = geometry.area;
+
+        // Each label is right-aligned in the label column, on the row it
+        // describes. Max at the top, min at the bottom
+        let mut labels = labels.into_iter();
+        let mut render_label = |y: u16| {
+            if let Some(label) = labels.next() {
+                render_y_label(buf, label, label_area, y);
+            }
+        };
+        match bars_height {
+            0 => {}
+            1 => render_label(bars_area.top()),
+            _ => {
+                render_label(bars_area.top());
+                render_label(bars_area.bottom() - 1);
+            }
+        }
+        if amount_height > 0 {
+            render_label(amount_area.top());
+        }
+        if uv_height > 0 {
+            render_y_label(buf, Line::from("UV"), label_area, uv_area.y);
+        }
+
+        if let Some(coordinates) = self.coordinates {
+            shade_daylight(buf, geometry, coordinates);
+        }
+        if !geometry.area.is_empty() {
+            annotate_temperatures(
+                buf,
+                self.forecast,
+                &periods,
+                geometry,
+                label_area,
+            );
+        }
+        render_icons(
+            buf,
+            &periods,
+            geometry,
+            icon_area,
+            (uv_height > 0).then_some(uv_area),
+            self.coordinates,
+            self.icons,
+        );
+
+        // Bars line up column-for-column with the chart above
+        let column_area = |area: Rect| Rect {
+            x: graph_area.x,
+            width: graph_area.width,
+            ..area
+        };
+        let period_values = |f: &dyn Fn(&ForecastPeriod) -> u64| {
+            graph_area
+                .columns()
+                .map(|column| {
+                    let x = geometry.column_to_x(column.x);
+                    // Find the period that contains this column
+                    periods
+                        .iter()
+                        .rev()
+                        .find(|period| {
+                            period.start_time().timestamp() as f64 <= x
+                        })
+                        .map(|period| f(period))
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<u64>>()
+        };
+        if bars_height > 0 {
+            Sparkline::default()
+                .data(period_values(&|period| period.pop() as u64))
+                .max(100)
                 .style(STYLES.weather_line_precipitation)
-                .data(&precips),
-            Dataset::default()
-                .name("temp")
-                .marker(symbols::Marker::Braille)
-                .graph_type(GraphType::Line)
-                .style(STYLES.weather_line_temperature)
-                .data(&temps),
-        ];
-
-        // Build the axes
-        let x_axis = Axis::default()
-            .style(Style::default().white())
-            .bounds([min_x, max_x])
-            // 4 evenly spaced labels
-            .labels(labels.into_iter().step_by(PERIODS / 3));
-        let y_axis = Axis::default()
-            .style(Style::default().white())
-            .bounds([min_temp, max_temp])
-            .labels([format!("{min_temp:.0}°"), format!("{max_temp:.0}°")])
-            .labels_alignment(Alignment::Right);
-
-        // Create the chart and link all the parts together
-        let chart = Chart::new(datasets).x_axis(x_axis).y_axis(y_axis);
-
-        chart.render(area, buf);
+                .render(column_area(bars_area), buf);
+        }
+        if let (Some(max_amount), 1) = (max_amount, amount_height) {
+            // Sparklines need integers, so scale up to keep precision
+            const SCALE: f64 = 100.0;
+            Sparkline::default()
+                .data(period_values(&|period| {
+                    (period.precipitation_amount().unwrap_or_default() * SCALE)
+                        as u64
+                }))
+                .max((max_amount * SCALE) as u64)
+                .style(STYLES.weather_precipitation_amount)
+                .render(column_area(amount_area), buf);
+        }
+    }
+}
+
+/// Logged temperatures over the past week
+struct HistoryChart {
+    temperatures: Vec<(DateTime<Utc>, f64)>,
+    unit: TemperatureUnit,
+}
+
+impl HistoryChart {
+    /// How many days back to show
+    const DAYS: i64 = 7;
+}
+
+impl Widget for HistoryChart {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let Some((start, _)) = self.temperatures.first() else {
+            Line::from("No history yet").render(area, buf);
+            return;
+        };
+        let temps: Vec<(f64, f64)> = self
+            .temperatures
+            .iter()
+            .map(|(time, temp)| (time.timestamp() as f64, *temp))
+            .collect();
+
+        // Each midnight gets a marker and a day label
+        let start = start.with_timezone(&Local);
+        let midnights: Vec<DateTime<Local>> = (1..=HistoryChart::DAYS)
+            .filter_map(|days| {
+                let date = start.date_naive() + TimeDelta::days(days);
+                date.and_hms_opt(0, 0, 0)?
+                    .and_local_timezone(Local)
+                    .earliest()
+            })
+            .filter(|midnight| {
+                temps
+                    .last()
+                    .is_some_and(|(x, _)| (midnight.timestamp() as f64) < *x)
+            })
+            .collect();
+
+        let chart = TemperatureChart {
+            temps: &temps,
+            unit: self.unit,
+            times: &midnights,
+        };
+        let label_width = chart
+            .labels()
+            .iter()
+            .map(Line::width)
+            .max()
+            .unwrap_or_default() as u16;
+        let [plot_area, x_label_area] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)])
+                .areas(area);
+        chart.render(buf, plot_area, x_label_area, label_width, Vec::new());
+    }
+}
+
+/// The parts of a temperature chart that the forecast and history charts
+/// share: max/min labels in a column on the left, axes, a marker at each
+/// midnight, the temperature line, and time labels along the bottom. We draw
+/// the axes and labels ourselves rather than letting the chart do it, so we
+/// know exactly which column each timestamp lands in
+struct TemperatureChart<'a> {
+    /// (timestamp, temperature) points, oldest first
+    temps: &'a [(f64, f64)],
+    unit: TemperatureUnit,
+    /// Times to label along the bottom, if they fit. Midnights get a marker
+    times: &'a [DateTime<Local>],
+}
+
+impl TemperatureChart<'_> {
+    /// Bound the x axis to the data
+    fn x_bounds(&self) -> [f64; 2] {
+        match (self.temps.first(), self.temps.last()) {
+            (Some((first, _)), Some((last, _))) => [*first, *last],
+            _ => [0.0, 0.0],
+        }
+    }
+
+    /// Bound the y axis to the temperatures, so it zooms in as much as
+    /// possible
+    fn y_bounds(&self) -> [f64; 2] {
+        match self.temps.iter().map(|(_, temp)| *temp).minmax() {
+            MinMaxResult::NoElements => [0.0, 0.0],
+            MinMaxResult::OneElement(value) => [value, value],
+            MinMaxResult::MinMax(min, max) => [min, max],
+        }
+    }
+
+    /// Labels for the max and min temperatures
+    fn labels(&self) -> [Line<'static>; 2] {
+        let [min, max] = self.y_bounds();
+        let unit = self.unit;
+        [
+            Line::from(format!("{max:.0}{unit}")),
+            Line::from(format!("{min:.0}{unit}")),
+        ]
+    }
+
+    /// Draw the chart in `plot_area`, with time labels below it in
+    /// `x_label_area`. The label column is `label_width` wide, so callers can
+    /// make room for labels of their own. `datasets` are drawn on top of the
+    /// midnight markers and under the temperature line. Returns the label
+    /// column and the geometry of the graph, or `None` if there's no width or
+    /// no span of time to draw. If there's no height, the geometry is still
+    /// returned so callers can line up rows below the chart
+    fn render(
+        self,
+        buf: &mut Buffer,
+        plot_area: Rect,
+        x_label_area: Rect,
+        label_width: u16,
+        datasets: Vec<Dataset<'_>>,
+    ) -> Option<(Rect, ChartGeometry)> {
+        let x_bounds = self.x_bounds();
+        let y_bounds = self.y_bounds();
+
+        let [label_area, axes_area] = Layout::horizontal([
+            Constraint::Length(label_width),
+            Constraint::Min(0),
+        ])
+        .areas(plot_area);
+        let axes = Block::new()
+            .borders(Borders::LEFT | Borders::BOTTOM)
+            .border_style(STYLES.weather_axis);
+        let graph_area = axes.inner(axes_area);
+        axes.render(axes_area, buf);
+        if graph_area.width == 0 {
+            return None;
+        }
+        if graph_area.height > 0 {
+            self.render_plot(buf, graph_area, label_area, datasets);
+        }
+
+        if x_bounds[0] >= x_bounds[1] {
+            return None;
+        }
+        let geometry = ChartGeometry {
+            area: graph_area,
+            x_bounds,
+            y_bounds,
+        };
+        render_x_labels(buf, self.times, geometry, x_label_area);
+        Some((label_area, geometry))
+    }
+
+    /// Draw the max/min labels, midnight markers, datasets, and temperature
+    /// line
+    fn render_plot(
+        &self,
+        buf: &mut Buffer,
+        graph_area: Rect,
+        label_area: Rect,
+        datasets: Vec<Dataset<'_>>,
+    ) {
+        let x_bounds = self.x_bounds();
+        let y_bounds = self.y_bounds();
+        let [max_label, min_label] = self.labels();
+        render_y_label(buf, max_label, label_area, graph_area.top());
+        render_y_label(buf, min_label, label_area, graph_area.bottom() - 1);
+
+        let midnight_markers: Vec<[(f64, f64); 2]> = self
+            .times
+            .iter()
+            .filter(|time| time.hour() == 0)
+            .map(|time| {
+                let x = time.timestamp() as f64;
+                [(x, y_bounds[0]), (x, y_bounds[1])]
+            })
+            .collect();
+        let datasets: Vec<Dataset> = midnight_markers
+            .iter()
+            .map(|data| {
+                Dataset::default()
+                    .marker(symbols::Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(STYLES.weather_midnight_marker)
+                    .data(data)
+            })
+            .chain(datasets)
+            .chain(iter::once(
+                Dataset::default()
+                    .name("temp")
+                    .marker(symbols::Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(STYLES.weather_line_temperature)
+                    .data(self.temps),
+            ))
+            .collect();
+        Chart::new(datasets)
+            .x_axis(Axis::default().bounds(x_bounds))
+            .y_axis(Axis::default().bounds(y_bounds))
+            .legend_position(None)
+            .render(graph_area, buf);
+    }
+}
+
+/// Render a label right-aligned in a chart's label column, on one row
+fn render_y_label(buf: &mut Buffer, label: Line, label_area: Rect, y: u16) {
+    label.right_aligned().style(STYLES.weather_axis).render(
+        Rect {
+            y,
+            height: 1,
+            ..label_area
+        },
+        buf,
+    );
+}
+
+impl IconSet {
+    /// Get the icon for a condition. Clear skies look different at night
+    fn icon(self, condition: Condition, daylight: Daylight) -> &'static str {
+        let day = daylight == Daylight::Day;
+        match (self, condition) {
+            (Self::Unicode, Condition::Unknown) => "?",
+            (Self::Unicode, Condition::Clear) if day => "☀",
+            (Self::Unicode, Condition::Clear) => "☾",
+            (Self::Unicode, Condition::PartlyCloudy) => "◐",
+            (Self::Unicode, Condition::Cloudy) => "☁",
+            (Self::Unicode, Condition::Fog) => "≡",
+            (Self::Unicode, Condition::Rain) => "☂",
+            (Self::Unicode, Condition::Snow) => "❄",
+            (Self::Unicode, Condition::Thunder) => "↯",
+            (Self::Ascii, Condition::Unknown) => "?",
+            (Self::Ascii, Condition::Clear) if day => "O",
+            (Self::Ascii, Condition::Clear) => "C",
+            (Self::Ascii, Condition::PartlyCloudy) => "%",
+            (Self::Ascii, Condition::Cloudy) => "#",
+            (Self::Ascii, Condition::Fog) => "=",
+            (Self::Ascii, Condition::Rain) => "/",
+            (Self::Ascii, Condition::Snow) => "*",
+            (Self::Ascii, Condition::Thunder) => "!",
+        }
+    }
+}
+
+/// Render a condition icon above the chart for each block of hours, and the
+/// block's peak UV index below that if available. Blocks are as small as
+/// possible while leaving a space between icons. Each block shows the most
+/// notable condition within it.
+fn render_icons(
+    buf: &mut Buffer,
+    periods: &[&ForecastPeriod],
+    geometry: ChartGeometry,
+    icon_area: Rect,
+    uv_area: Option<Rect>,
+    coordinates: Option<Coordinates>,
+    icons: IconSet,
+) {
+    /// Block lengths, in hours. These all divide evenly into a day
+    const STEPS: [u32; 7] = [1, 2, 3, 4, 6, 8, 12];
+
+    let max_icons = (geometry.area.width / 2) as usize;
+    let step = STEPS
+        .into_iter()
+        .find(|step| periods.len().div_ceil(*step as usize) <= max_icons)
+        .unwrap_or(24);
+
+    let blocks = periods.iter().chunk_by(|period| {
+        let time = period.start_time();
+        (time.date_naive(), time.hour() / step)
+    });
+    // Leftmost column the next icon can go in. Partial blocks at the edges
+    // can land too close to their neighbors
+    let mut next_column = icon_area.left();
+    for (_, block) in &blocks {
+        let block: Vec<_> = block.collect();
+        let Some(condition) = block.iter().map(|p| p.condition()).max() else {
+            continue;
+        };
+        // Put the icon in the middle of the block
+        let middle = block[block.len() / 2].start_time();
+        let daylight = coordinates.map_or(Daylight::Day, |coordinates| {
+            Daylight::at(middle.to_utc(), coordinates)
+        });
+        let column = geometry.x_to_column(middle.timestamp() as f64);
+        if column < next_column {
+            continue;
+        }
+        next_column = column + 2;
+        buf.set_string(
+            column,
+            icon_area.y,
+            icons.icon(condition, daylight),
+            STYLES.weather_icon,
+        );
+
+        let uv_index = block
+            .iter()
+            .filter_map(|period| period.uv_index())
+            .max_by(f64::total_cmp);
+        if let (Some(uv_area), Some(uv_index)) = (uv_area, uv_index) {
+            // Only one column to work with, so 10+ gets squished
+            let uv_index = uv_index.round() as u8;
+            let text = if uv_index < 10 {
+                uv_index.to_string()
+            } else {
+                "+".into()
+            };
+            let style = STYLES.uv_levels[match uv_index {
+                0..=2 => 0,
+                3..=5 => 1,
+                6..=7 => 2,
+                8..=10 => 3,
+                _ => 4,
+            }];
+            buf.set_string(column, uv_area.y, text, style);
+        }
+    }
+}
+
+/// Mark the high and low points on the temperature line, highlight the
+/// current temperature on the y axis, and show how tomorrow's high compares to
+/// today's if the chart crosses midnight
+fn annotate_temperatures(
+    buf: &mut Buffer,
+    forecast: &WeatherForecast,
+    periods: &[&ForecastPeriod],
+    geometry: ChartGeometry,
+    label_area: Rect,
+) {
+    let graph_area = geometry.area;
+    let position = |period: &ForecastPeriod| {
+        let x = period.start_time().timestamp() as f64;
+        (geometry.x_to_column(x), geometry.y_to_row(period.temp()))
+    };
+
+    // High/low markers, right on the line. If the temperature is flat,
+    // there's nothing to point out
+    if let MinMaxResult::MinMax(low, high) = periods
+        .iter()
+        .minmax_by(|a, b| a.temp().total_cmp(&b.temp()))
+    {
+        for (period, symbol) in [(high, "H"), (low, "L")] {
+            let (column, row) = position(period);
+            buf.set_string(column, row, symbol, STYLES.weather_high_low);
+        }
+    }
+
+    // Current temperature goes in the label column, level with the start of
+    // the line. On the top or bottom row it would cover the max/min label,
+    // which is within a row's worth of the current temperature anyway
+    if let Some(current) = periods.first()
+        && let (_, row) = position(current)
+        && row != graph_area.top()
+        && row != graph_area.bottom() - 1
+    {
+        let label = Line::from(current.temperature())
+            .right_aligned()
+            .style(STYLES.weather_current_temperature);
+        label.render(
+            Rect {
+                y: row,
+                height: 1,
+                ..label_area
+            },
+            buf,
+        );
+    }
+
+    // Compare today and tomorrow's highs, next to the first midnight marker
+    let midnight = periods
+        .iter()
+        .find(|period| period.start_time().hour() == 0);
+    if let Some(midnight) = midnight {
+        let tomorrow = midnight.start_time().date_naive();
+        let today = tomorrow.pred_opt().unwrap_or(tomorrow);
+        if let (Some(today_high), Some(tomorrow_high)) =
+            (forecast.high(today), forecast.high(tomorrow))
+        {
+            let delta = (tomorrow_high - today_high).round() as i64;
+            let delta = format!("{delta:+}°");
+            let width = delta.chars().count() as u16;
+            let (column, _) = position(midnight);
+            // Prefer the right side of the marker, so it reads as tomorrow
+            let column = if column + width < graph_area.right() {
+                column + 1
+            } else {
+                column.saturating_sub(width).max(graph_area.left())
+            };
+            buf.set_string(
+                column,
+                graph_area.top(),
+                delta,
+                STYLES.weather_high_delta,
+            );
+        }
+    }
+}
+
+/// Render hour labels below the chart, as many as will fit. Labels are
+/// placed on round hours (e.g. every 6 hours starting at midnight), and
+/// midnight is labelled with the day of the week instead.
+fn render_x_labels(
+    buf: &mut Buffer,
+    times: &[DateTime<Local>],
+    geometry: ChartGeometry,
+    label_area: Rect,
+) {
+    /// Hour intervals we're willing to label at. These all divide evenly into
+    /// a day, so the labels line up with midnight
+    const STEPS: [u32; 7] = [1, 2, 3, 4, 6, 8, 12];
+    /// Widest possible label, plus a space between it and the next label
+    const LABEL_WIDTH: u16 = 4;
+
+    let max_labels = (geometry.area.width / LABEL_WIDTH) as usize;
+    let step = STEPS
+        .into_iter()
+        .find(|step| times.len().div_ceil(*step as usize) <= max_labels)
+        .unwrap_or(24);
+
+    // Leftmost column the next label can start at, to prevent overlap
+    let mut next_column = label_area.left();
+    for time in times.iter().filter(|time| time.hour() % step == 0) {
+        let label = if time.hour() == 0 {
+            time.format("%a").to_string()
+        } else {
+            format_hour(*time)
+        };
+        let width = label.len() as u16;
+        // Center the label on its timestamp, but keep it on screen. Not
+        // clamp(), which panics if the label is wider than the area
+        let column = geometry
+            .x_to_column(time.timestamp() as f64)
+            .saturating_sub(width / 2)
+            .min(label_area.right().saturating_sub(width))
+            .max(label_area.left());
+        if column < next_column {
+            continue;
+        }
+        let style = if time.hour() == 0 {
+            STYLES.weather_midnight_label
+        } else {
+            STYLES.weather_axis
+        };
+        Line::from(label)
+            .style(style)
+            .render(Rect::new(column, label_area.y, width, 1), buf);
+        next_column = column + width + 1;
+    }
+}
+
+/// Shade the background of each column in the graph according to whether
+/// it's day or night at that time
+fn shade_daylight(
+    buf: &mut Buffer,
+    geometry: ChartGeometry,
+    coordinates: Coordinates,
+) {
+    for column in geometry.area.columns() {
+        let time = geometry.column_to_x(column.x) as i64;
+        let Some(time) = DateTime::from_timestamp(time, 0) else {
+            continue;
+        };
+        let style = match Daylight::at(time, coordinates) {
+            Daylight::Day => continue,
+            Daylight::Twilight => STYLES.weather_twilight,
+            Daylight::Night => STYLES.weather_night,
+        };
+        buf.set_style(column, style);
+    }
+}
+
+/// When is it going to rain/snow next, and how likely is it?
+fn precipitation_line(
+    forecast: &WeatherForecast,
+    threshold: f64,
+) -> Line<'static> {
+    let Some(event) = forecast.precipitation_event(threshold) else {
+        return Line::from("No precip next 24h");
+    };
+    let name = match event.condition {
+        Condition::Snow => "Snow",
+        Condition::Thunder => "Storms",
+        _ => "Rain",
+    };
+    let start = if event.start <= Utc::now() {
+        "now".to_owned()
+    } else {
+        format_hour(event.start.with_timezone(&Local))
+    };
+    let end = event.end.map(|end| {
+        (
+            format_hour(end.with_timezone(&Local)),
+            (end - event.start).num_hours(),
+        )
+    });
+    let text = precipitation_text(name, &start, end, event.peak);
+    Line::from(text).style(STYLES.weather_precipitation_summary)
+}
+
+/// Format a precipitation event, dropping the duration if the line won't fit
+/// on screen, e.g. "Rain 6a-9a (3h) 80%"
+fn precipitation_text(
+    name: &str,
+    start: &str,
+    end: Option<(String, i64)>,
+    peak: f64,
+) -> String {
+    let Some((end, hours)) = end else {
+        return format!("{name} from {start} {peak:.0}%");
+    };
+    let text = format!("{name} {start}-{end} ({hours}h) {peak:.0}%");
+    if text.len() <= usize::from(DIMENSIONS.width) {
+        text
+    } else {
+        format!("{name} {start}-{end} {peak:.0}%")
+    }
+}
+
+/// Compact summary of a forecast warning, e.g. "Freeze 3a 28°F"
+fn warning_line(warning: &Warning) -> Line<'static> {
+    Line::from(format!(
+        "{} {} {}",
+        warning.kind,
+        format_hour(warning.start.with_timezone(&Local)),
+        warning.extreme,
+    ))
+    .style(STYLES.weather_warning)
+}
+
+/// Compare the current temperature to the same time yesterday. `None` if we
+/// don't have a record from yesterday
+fn yesterday_line(state: &State) -> Option<Line<'static>> {
+    let forecast = state.weather();
+    let current = forecast.periods().next()?;
+    let yesterday = state.history.temperature_at(
+        state.location_name(),
+        current.start_time().to_utc() - TimeDelta::days(1),
+        forecast.temperature_unit(),
+    )?;
+    let delta = (current.temp() - yesterday).round() as i64;
+    let text = match delta {
+        0 => "Same as yesterday".to_owned(),
+        1.. => format!("{delta}° warmer vs yesterday"),
+        _ => format!("{}° cooler vs yesterday", -delta),
+    };
+    Some(Line::from(text))
+}
+
+/// Recommended time to go outside
+fn outside_line(
+    forecast: &WeatherForecast,
+    coordinates: Coordinates,
+) -> Line<'static> {
+    match forecast.best_time_outside(coordinates) {
+        Some(window) => Line::from(format!(
+            "Go out {}-{} {}",
+            format_hour(window.start),
+            format_hour(window.end),
+            window.best.temperature(),
+        )),
+        None => Line::from("Go out: --"),
+    }
+}
+
+/// Sunrise and sunset times for today
+fn sun_line(coordinates: Coordinates) -> Line<'static> {
+    let today = Local::now().date_naive();
+    match sun::sunrise_sunset(today, coordinates) {
+        Some((sunrise, sunset)) => Line::from(format!(
+            "Rise {}  Set {}",
+            format_time(sunrise.with_timezone(&Local)),
+            format_time(sunset.with_timezone(&Local)),
+        )),
+        // Polar day/night. Check what's going on at noon
+        None => match Daylight::at(
+            Local::now()
+                .with_hour(12)
+                .unwrap_or_else(Local::now)
+                .to_utc(),
+            coordinates,
+        ) {
+            Daylight::Day => Line::from("Sun up all day"),
+            Daylight::Twilight | Daylight::Night => {
+                Line::from("Sun down all day")
+            }
+        },
+    }
+}
+
+/// Current moon phase and the next full/new moon, whichever is sooner
+fn moon_line() -> Line<'static> {
+    let moon = MoonPhase::at(Utc::now());
+    let (next_name, next_time) = if moon.next_full_moon < moon.next_new_moon {
+        ("Full", moon.next_full_moon)
+    } else {
+        ("New", moon.next_new_moon)
+    };
+    Line::from(format!(
+        "{} {:.0}% {next_name} {}",
+        moon.phase.short_name(),
+        moon.illumination * 100.0,
+        next_time.with_timezone(&Local).format("%-m/%-d"),
+    ))
+}
+
+/// Format an hour of the day compactly, e.g. "6a"
+fn format_hour(time: DateTime<Local>) -> String {
+    let mut formatted = time.format("%-I%P").to_string();
+    formatted.pop(); // Remove the 'm' from 'am'/'pm'
+    formatted
+}
+
+/// Format a time of day compactly, e.g. "6:05a"
+fn format_time(time: DateTime<Local>) -> String {
+    let mut formatted = time.format("%-I:%M%P").to_string();
+    formatted.pop(); // Remove the 'm' from 'am'/'pm'
+    formatted
+}
+
+/// Maps between data values and cells within a chart's graph area
+#[derive(Copy, Clone, Debug)]
+struct ChartGeometry {
+    area: Rect,
+    x_bounds: [f64; 2],
+    y_bounds: [f64; 2],
+}
+
+impl ChartGeometry {
+    /// Map an x value to the column it falls in
+    fn x_to_column(&self, x: f64) -> u16 {
+        let [min, max] = self.x_bounds;
+        let span = (self.area.width - 1) as f64;
+        self.area.left() + ((x - min) / (max - min) * span).round() as u16
+    }
+
+    /// Map a column to the x value it represents
+    fn column_to_x(&self, column: u16) -> f64 {
+        let [min, max] = self.x_bounds;
+        let span = (self.area.width - 1).max(1) as f64;
+        min + (column - self.area.left()) as f64 / span * (max - min)
+    }
+
+    /// Map a y value to the row it falls in
+    fn y_to_row(&self, y: f64) -> u16 {
+        let [min, max] = self.y_bounds;
+        if max <= min {
+            return self.area.top();
+        }
+        let span = (self.area.height - 1) as f64;
+        self.area.top() + ((max - y) / (max - min) * span).round() as u16
     }
 }
 
@@ -163,10 +1402,72 @@ fn index_of<T: PartialEq>(list: &[T], value: T) -> Option<usize> {
 struct Styles {
     /// Highlighted tab name
     tab_highlight: Style,
+    /// Colors for each UV index level (low through extreme), per the WHO
+    /// scale
+    uv_levels: [Style; 5],
+    /// Heading for a group of lines
+    section_header: Style,
+    /// Current page out of total, for views that paginate
+    page_indicator: Style,
+    /// Colors for each AQI category, from best to worst. These are the
+    /// official EPA colors, or close to them
+    aqi_categories: [Style; 6],
     /// Transit line names (e.g. "86")
     transit_line_name: Style,
+    /// Departure time from a schedule, with no real-time prediction
+    transit_scheduled: Style,
+    /// Trip that was cancelled, shown in place of its scheduled time
+    transit_cancelled: Style,
+    /// Real-time departures, by how they compare to the schedule
+    transit_on_time: Style,
+    transit_late: Style,
+    transit_very_late: Style,
+    /// Departure to leave for now
+    transit_leave_for: Style,
+    /// Countdown until it's time to leave for a departure
+    transit_leave: Style,
+    /// Colors for each crowding level, from emptiest to fullest
+    transit_crowding: [Style; 3],
+    /// Service alert marker on a transit line
+    transit_alert: Style,
+    /// Service alert at or above [Alert::SEVERE], e.g. a suspension
+    transit_alert_severe: Style,
+    /// Bike-share station with nothing left
+    bike_empty: Style,
+    /// Bike-share station that's running low
+    bike_low: Style,
+    /// Bike-share station that isn't renting or taking returns
+    bike_closed: Style,
+    /// Axis lines and labels on the weather graph
+    weather_axis: Style,
+    /// Background for nighttime on the weather graph
+    weather_night: Style,
+    /// Background for twilight on the weather graph
+    weather_twilight: Style,
+    /// Vertical line at each midnight on the weather graph
+    weather_midnight_marker: Style,
+    /// Day label at each midnight on the weather graph
+    weather_midnight_label: Style,
+    /// Vertical line at the current time on the weather graph
+    weather_now_marker: Style,
     /// Precipitation line on the weather graph
     weather_line_precipitation: Style,
+    /// Upcoming precipitation summary below the weather graph
+    weather_precipitation_summary: Style,
+    /// Forecast warning line below the weather graph
+    weather_warning: Style,
+    /// Name of the weather location being shown, when there's more than one
+    weather_location: Style,
+    /// Precipitation amount bars below the weather graph
+    weather_precipitation_amount: Style,
+    /// Condition icons above the weather graph
+    weather_icon: Style,
+    /// High/low markers on the weather graph
+    weather_high_low: Style,
+    /// Current temperature label on the weather graph's y axis
+    weather_current_temperature: Style,
+    /// Difference between today and tomorrow's highs on the weather graph
+    weather_high_delta: Style,
     /// Temperature line on the weather graph
     weather_line_temperature: Style,
 }
@@ -178,9 +1479,214 @@ impl Default for Styles {
                 .fg(Color::Cyan)
                 .bg(Color::Black)
                 .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            uv_levels: [
+                Style::default().green(),
+                Style::default().yellow(),
+                Style::default().fg(Color::Indexed(208)), // Orange
+                Style::default().red(),
+                Style::default().magenta(),
+            ],
+            section_header: Style::default().add_modifier(Modifier::BOLD),
+            page_indicator: Style::default().dark_gray(),
+            aqi_categories: [
+                Style::default().green(),
+                Style::default().yellow(),
+                Style::default().fg(Color::Indexed(208)), // Orange
+                Style::default().red(),
+                Style::default().magenta(),
+                Style::default().fg(Color::Indexed(88)), // Maroon
+            ],
             transit_line_name: Style::default().add_modifier(Modifier::BOLD),
+            transit_scheduled: Style::default()
+                .dark_gray()
+                .add_modifier(Modifier::ITALIC),
+            transit_cancelled: Style::default().red(),
+            transit_on_time: Style::default().green(),
+            transit_late: Style::default().yellow(),
+            transit_very_late: Style::default().light_red(),
+            transit_leave_for: Style::default()
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            transit_leave: Style::default()
+                .green()
+                .add_modifier(Modifier::BOLD),
+            transit_crowding: [
+                Style::default().green(),
+                Style::default().yellow(),
+                Style::default().red(),
+            ],
+            transit_alert: Style::default().yellow(),
+            transit_alert_severe: Style::default()
+                .light_red()
+                .add_modifier(Modifier::BOLD),
+            bike_empty: Style::default().red(),
+            bike_low: Style::default().yellow(),
+            bike_closed: Style::default().dark_gray(),
+            weather_axis: Style::default().white(),
+            weather_night: Style::default().bg(Color::Indexed(235)),
+            weather_twilight: Style::default().bg(Color::Indexed(238)),
+            weather_midnight_marker: Style::default().dark_gray(),
+            weather_midnight_label: Style::default()
+                .white()
+                .add_modifier(Modifier::BOLD),
+            weather_now_marker: Style::default().yellow(),
             weather_line_precipitation: Style::default().blue(),
+            weather_precipitation_summary: Style::default().light_blue(),
+            weather_location: Style::default().add_modifier(Modifier::BOLD),
+            weather_warning: Style::default()
+                .black()
+                .on_light_red()
+                .add_modifier(Modifier::BOLD),
+            weather_precipitation_amount: Style::default().cyan(),
+            weather_icon: Style::default().white(),
+            weather_high_low: Style::default()
+                .light_red()
+                .add_modifier(Modifier::BOLD),
+            weather_current_temperature: Style::default()
+                .yellow()
+                .add_modifier(Modifier::BOLD),
+            weather_high_delta: Style::default().light_magenta(),
             weather_line_temperature: Style::default().red(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{transit::tests::line, weather::tests::rainy_day};
+
+    /// Build lines that take up the given number of rows each
+    fn pages(rows: &[usize]) -> Vec<Range<usize>> {
+        let lines = rows
+            .iter()
+            .map(|rows| {
+                // Label and trailing blank, plus a row for each stop
+                let stops = vec![&[5][..]; rows - 2];
+                line("line", &stops)
+            })
+            .collect();
+        transit_pages(&TransitPredictions { lines })
+    }
+
+    /// There are 10 rows for lines
+    #[test]
+    fn test_transit_pages() {
+        assert_eq!(pages(&[]), vec![0..0]);
+        assert_eq!(pages(&[4, 4]), vec![0..2]);
+        // The second line's trailing blank can fall off the bottom
+        assert_eq!(pages(&[5, 6]), vec![0..2]);
+        assert_eq!(pages(&[5, 7]), vec![0..1, 1..2]);
+        assert_eq!(pages(&[4, 4, 4, 4]), vec![0..2, 2..4]);
+        // Alert-only lines are just a label and blank
+        assert_eq!(pages(&[2, 2, 2, 2, 2]), vec![0..5]);
+        assert_eq!(pages(&[2, 2, 2, 2, 2, 2]), vec![0..5, 5..6]);
+    }
+
+    /// A line too tall for the screen gets a page to itself
+    #[test]
+    fn test_transit_pages_tall_line() {
+        assert_eq!(pages(&[15]), vec![0..1]);
+        assert_eq!(pages(&[3, 15, 3]), vec![0..1, 1..2, 2..3]);
+    }
+
+    #[test]
+    fn test_precipitation_text() {
+        let text = |name, start, end: Option<(&str, i64)>, peak| {
+            let end = end.map(|(end, hours)| (end.to_owned(), hours));
+            precipitation_text(name, start, end, peak)
+        };
+        assert_eq!(
+            text("Rain", "6a", Some(("9a", 3)), 80.0),
+            "Rain 6a-9a (3h) 80%"
+        );
+        assert_eq!(
+            text("Storms", "10p", Some(("11p", 1)), 100.0),
+            "Storms 10p-11p (1h) 100%"
+        );
+        // Longest case is one column too many, so the duration goes
+        assert_eq!(
+            text("Storms", "10p", Some(("11a", 13)), 100.0),
+            "Storms 10p-11a 100%"
+        );
+        assert_eq!(text("Storms", "10p", None, 100.0), "Storms from 10p 100%");
+    }
+
+    /// Render the weather chart in bar mode, with UV and amounts
+    fn weather_chart(height: u16) -> Vec<String> {
+        let forecast = rainy_day();
+        let area = Rect::new(0, 0, DIMENSIONS.width, height);
+        let mut buf = Buffer::empty(area);
+        WeatherChart {
+            forecast: &forecast,
+            coordinates: None,
+            precipitation_display: PrecipitationDisplay::Bars,
+            icons: IconSet::Ascii,
+        }
+        .render(area, &mut buf);
+        area.rows()
+            .map(|row| {
+                row.columns()
+                    .map(|cell| buf[(cell.x, cell.y)].symbol())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// First word of each row. Times along the bottom depend on the local
+    /// timezone, so that row is just checked for something
+    fn row_labels(rows: &[String]) -> Vec<&str> {
+        rows.iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let label = row.split_whitespace().next().unwrap_or_default();
+                if i == rows.len() - 1 && !label.is_empty() {
+                    "…"
+                } else {
+                    label
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_weather_chart_rows() {
+        let rows = weather_chart(9);
+        let labels = row_labels(&rows);
+        // Everything fits: icons, UV, plot, bars, amount, and x labels
+        assert_eq!(
+            labels,
+            [
+                "/",
+                "UV",
+                "60°F│L",
+                "60°F│",
+                "└─────────────────",
+                "100%",
+                "0%",
+                "0.10in",
+                "…"
+            ]
+        );
+    }
+
+    /// With a warning taking a row, the amount goes and the bars shrink so
+    /// the plot still fits
+    #[test]
+    fn test_weather_chart_rows_squeezed() {
+        let rows = weather_chart(7);
+        let labels = row_labels(&rows);
+        assert_eq!(
+            labels,
+            [
+                "/",
+                "UV",
+                "60°F│L",
+                "60°F│",
+                "└───────────────────",
+                "%",
+                "…"
+            ]
+        );
+        assert!(rows[5].contains('▆'));
+    }
+}
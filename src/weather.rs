@@ -1,70 +1,260 @@
+mod nws;
+mod open_meteo;
+mod warnings;
+
 use crate::{
-    config::Config,
+    config::{Config, Coordinates},
     state::{Message, Tx},
-    util::http_get,
+    sun::Daylight,
+    units::{PrecipitationUnit, SpeedUnit, TemperatureUnit},
+    weather::{nws::Nws, open_meteo::OpenMeteo},
 };
-use chrono::{DateTime, Local, Utc};
+use chrono::{DateTime, Local, NaiveDate, Utc};
 use serde::Deserialize;
-use std::{thread, time::Duration};
+use std::{collections::HashMap, thread, time::Duration};
+
+pub use warnings::{Warning, WarningConfig};
 
 /// Time between requests
 const DATA_TTL: Duration = Duration::from_secs(60);
-const API_HOST: &str = "https://api.weather.gov";
-
-/// Fetch weather in a loop. When we get a new forecast, send a message to
-/// update state
-pub fn weather_loop(config: Config, tx: Tx) {
-    let url = format!(
-        "{}/gridpoints/{}/{},{}/forecast/hourly",
-        API_HOST,
-        config.forecast_office,
-        config.forecast_gridpoint.0,
-        config.forecast_gridpoint.1
-    );
 
+/// Fetch weather for one location in a loop. When we get a new forecast, send
+/// a message to update state. `index` is the location's position in
+/// [Config::locations]
+pub fn weather_loop(config: Config, tx: Tx, index: usize) {
+    let location = &config.locations()[index];
+    let provider: Box<dyn WeatherProvider> = match &location.weather {
+        WeatherConfig::Nws { office, gridpoint } => {
+            Box::new(Nws::new(office, *gridpoint, config.units))
+        }
+        WeatherConfig::OpenMeteo => {
+            // Config loading makes sure Open-Meteo locations have them
+            let coordinates = location.coordinates.unwrap();
+            Box::new(OpenMeteo::new(coordinates, config.units))
+        }
+    };
+
+    let mut warnings = Vec::new();
     loop {
-        if let Ok(weather) = http_get(&url) {
+        if let Ok(weather) = provider.fetch() {
+            // Notify for any warnings that weren't in the last forecast
+            let new_warnings = config.weather_warnings.evaluate(&weather);
+            config.weather_warnings.notify(
+                &location.name,
+                &warnings,
+                &new_warnings,
+            );
+            warnings = new_warnings;
             // We have a new forecast. Update state
-            tx.send(Message::Weather(weather));
+            tx.send(Message::Weather { index, weather });
         }
         thread::sleep(DATA_TTL);
     }
 }
 
-/// Weather is a phenomenon where food and fruit and shit falls from the sky
-///
-/// https://www.weather.gov/documentation/services-web-api#/default/gridpoint_forecast
-#[derive(Clone, Debug, Default, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct WeatherForecast {
-    properties: ForecastProperties,
+/// Where to fetch weather data from
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "provider", rename_all = "snake_case")]
+pub enum WeatherConfig {
+    /// National Weather Service (US only)
+    Nws {
+        /// Forecast office ID, e.g. "BOX"
+        office: String,
+        /// x/y coordinates within the office's forecast grid
+        gridpoint: (u32, u32),
+    },
+    /// Open-Meteo (global). Uses the location's coordinates
+    OpenMeteo,
+}
+
+/// A source of hourly forecast data
+pub trait WeatherProvider {
+    /// Fetch the latest forecast. Errors are logged by the provider
+    fn fetch(&self) -> Result<WeatherForecast, ()>;
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ForecastProperties {
+/// Hourly weather forecast, converted from whichever provider it came from
+#[derive(Clone, Debug, Default)]
+pub struct WeatherForecast {
     periods: Vec<ForecastPeriod>,
+    /// Full-day high temperature for each local date, including hours that
+    /// have already passed. Not every provider has this
+    highs: HashMap<NaiveDate, f64>,
 }
 
-#[derive(Clone, Debug, PartialEq, Deserialize)]
-#[serde(rename_all = "camelCase")]
+/// Forecast for a single hour
+#[derive(Clone, Debug, PartialEq)]
 pub struct ForecastPeriod {
     start_time: DateTime<Utc>,
     end_time: DateTime<Utc>,
-    temperature: i32,
-    probability_of_precipitation: Unit,
+    temperature: f64,
+    temperature_unit: TemperatureUnit,
+    /// Percentage, 0-100
+    probability_of_precipitation: Option<f64>,
+    /// Liquid-equivalent precipitation over the period. Not every provider
+    /// has this
+    precipitation_amount: Option<f64>,
+    precipitation_unit: PrecipitationUnit,
+    condition: Condition,
+    /// Not every provider has this
+    uv_index: Option<f64>,
+    /// Peak wind gust speed. Not every provider has this
+    wind_gust: Option<f64>,
+    speed_unit: SpeedUnit,
+}
+
+/// A span of time that's nice to be outside
+#[derive(Clone, Debug)]
+pub struct OutsideWindow {
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    /// The nicest hour in the window
+    pub best: ForecastPeriod,
+}
+
+/// A stretch of time where precipitation is likely
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrecipitationEvent {
+    /// Most notable condition during the event
+    pub condition: Condition,
+    pub start: DateTime<Utc>,
+    /// `None` if the event lasts past the end of the forecast window
+    pub end: Option<DateTime<Utc>>,
+    /// Highest probability of precipitation during the event, 0-100
+    pub peak: f64,
 }
 
-#[derive(Clone, Debug, PartialEq, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Unit {
-    pub value: Option<i32>,
+/// General sky/weather conditions, boiled down from whatever the provider
+/// gives us. Ordered from least to most notable, so the most notable condition
+/// in a span of time is the max
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Condition {
+    #[default]
+    Unknown,
+    Clear,
+    PartlyCloudy,
+    Cloudy,
+    Fog,
+    Rain,
+    Snow,
+    Thunder,
 }
 
 impl WeatherForecast {
     /// Get all periods in the forecast
     pub fn periods(&self) -> impl '_ + Iterator<Item = &ForecastPeriod> {
-        self.properties.periods.iter()
+        self.periods.iter()
+    }
+
+    /// Highest forecasted temperature on a local date. The hourly periods
+    /// only cover what's left of today, so use the provider's full-day high
+    /// too if it has one
+    pub fn high(&self, date: NaiveDate) -> Option<f64> {
+        self.periods()
+            .filter(|period| period.start_time().date_naive() == date)
+            .map(ForecastPeriod::temp)
+            .chain(self.highs.get(&date).copied())
+            .max_by(f64::total_cmp)
+    }
+
+    /// Find the best span of daylight hours to go outside within the next day,
+    /// based on temperature, precipitation, and UV. The window is centered on
+    /// the best hour, and extends to neighboring hours that are nearly as good
+    pub fn best_time_outside(
+        &self,
+        coordinates: Coordinates,
+    ) -> Option<OutsideWindow> {
+        /// How many hours ahead to look
+        const HOURS: usize = 24;
+        /// How much worse than the best hour a neighbor can be and still be
+        /// included in the window
+        const TOLERANCE: f64 = 5.0;
+
+        let hours: Vec<(&ForecastPeriod, f64)> = self
+            .periods()
+            .take(HOURS)
+            .map(|period| {
+                // Nobody wants to go for a walk at 3am
+                let penalty = match Daylight::at(period.start_time, coordinates)
+                {
+                    Daylight::Day => period.outside_penalty(),
+                    Daylight::Twilight | Daylight::Night => f64::INFINITY,
+                };
+                (period, penalty)
+            })
+            .collect();
+        let (best, (best_period, best_penalty)) = hours
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, penalty))| penalty.is_finite())
+            .min_by(|(_, (_, a)), (_, (_, b))| a.total_cmp(b))?;
+
+        let acceptable = |(_, penalty): &&(&ForecastPeriod, f64)| {
+            *penalty <= best_penalty + TOLERANCE
+        };
+        let before = hours[..best].iter().rev().take_while(acceptable).count();
+        let after = hours[best + 1..].iter().take_while(acceptable).count();
+        Some(OutsideWindow {
+            start: hours[best - before].0.start_time(),
+            end: hours[best + after].0.end_time.with_timezone(&Local),
+            best: (*best_period).clone(),
+        })
+    }
+
+    /// Find the first stretch of the next day where the probability of
+    /// precipitation is at least `threshold` percent
+    pub fn precipitation_event(
+        &self,
+        threshold: f64,
+    ) -> Option<PrecipitationEvent> {
+        /// How many hours ahead to look
+        const HOURS: usize = 24;
+
+        let mut periods = self.periods().take(HOURS).peekable();
+        // Skip to the first period over the threshold, then take everything
+        // until it drops back below
+        while periods.next_if(|period| period.pop() < threshold).is_some() {}
+        let mut event: Vec<&ForecastPeriod> = Vec::new();
+        while let Some(period) =
+            periods.next_if(|period| period.pop() >= threshold)
+        {
+            event.push(period);
+        }
+        let first = event.first()?;
+        let last = event.last()?;
+        // If we ran out of periods, we don't know when it ends
+        let end = periods.peek().map(|_| last.end_time);
+
+        Some(PrecipitationEvent {
+            condition: event
+                .iter()
+                .map(|period| period.condition)
+                .max()
+                .unwrap_or_default(),
+            start: first.start_time,
+            end,
+            peak: event
+                .iter()
+                .map(|period| period.pop())
+                .max_by(f64::total_cmp)
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Unit that all temperatures in this forecast are in
+    pub fn temperature_unit(&self) -> TemperatureUnit {
+        self.periods()
+            .next()
+            .map(|period| period.temperature_unit)
+            .unwrap_or_default()
+    }
+
+    /// Unit that all wind speeds in this forecast are in
+    pub fn speed_unit(&self) -> SpeedUnit {
+        self.periods()
+            .next()
+            .map(|period| period.speed_unit)
+            .unwrap_or_default()
     }
 }
 
@@ -74,26 +264,310 @@ impl ForecastPeriod {
         self.start_time.with_timezone(&Local)
     }
 
-    /// TODO
-    pub fn temp(&self) -> i32 {
+    /// Temperature, in [Self::temperature_unit]
+    pub fn temp(&self) -> f64 {
         self.temperature
     }
 
-    /// TODO
-    pub fn pop(&self) -> i32 {
-        self.probability_of_precipitation.value.unwrap_or_default()
+    /// Probability of precipitation, 0-100
+    pub fn pop(&self) -> f64 {
+        self.probability_of_precipitation.unwrap_or_default()
+    }
+
+    /// Precipitation amount, in [Self::precipitation_unit], if the provider
+    /// gives it
+    pub fn precipitation_amount(&self) -> Option<f64> {
+        self.precipitation_amount
+    }
+
+    pub fn precipitation_unit(&self) -> PrecipitationUnit {
+        self.precipitation_unit
+    }
+
+    pub fn condition(&self) -> Condition {
+        self.condition
+    }
+
+    pub fn uv_index(&self) -> Option<f64> {
+        self.uv_index
+    }
+
+    /// Wind gust speed, in [WeatherForecast::speed_unit], if the provider gives it
+    pub fn wind_gust(&self) -> Option<f64> {
+        self.wind_gust
+    }
+
+    /// How unpleasant is it to be outside during this period? 0 is perfect,
+    /// higher is worse. Combines temperature, precipitation, and UV
+    fn outside_penalty(&self) -> f64 {
+        /// Comfortable temperature range, in °F
+        const COMFORTABLE: (f64, f64) = (60.0, 78.0);
+        /// UV index at which sunburn becomes a concern
+        const UV_LIMIT: f64 = 5.0;
+
+        let temp = self.temperature_unit.to_fahrenheit(self.temperature);
+        let temp_penalty = if temp < COMFORTABLE.0 {
+            COMFORTABLE.0 - temp
+        } else {
+            (temp - COMFORTABLE.1).max(0.0)
+        };
+        let precip_penalty = self.pop() / 2.0;
+        let uv_penalty =
+            (self.uv_index.unwrap_or_default() - UV_LIMIT).max(0.0);
+        temp_penalty + precip_penalty + uv_penalty * 5.0
     }
 
     /// Formatted temperature
     pub fn temperature(&self) -> String {
-        format!("{:.0}°", self.temperature)
+        format!("{:.0}{}", self.temperature, self.temperature_unit)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    /// Build an hourly forecast starting at a fixed time, one period per
+    /// value. Each period is 60°F, dry, and cloudy, until `f` modifies it
+    /// with its value
+    pub(crate) fn hourly<T>(
+        values: &[T],
+        f: impl Fn(&mut ForecastPeriod, &T),
+    ) -> WeatherForecast {
+        let periods = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let start_time = hour(i as i64);
+                let mut period = ForecastPeriod {
+                    start_time,
+                    end_time: start_time + TimeDelta::hours(1),
+                    temperature: 60.0,
+                    temperature_unit: TemperatureUnit::Fahrenheit,
+                    probability_of_precipitation: Some(0.0),
+                    precipitation_amount: None,
+                    precipitation_unit: PrecipitationUnit::Inches,
+                    condition: Condition::Cloudy,
+                    uv_index: None,
+                    wind_gust: None,
+                    speed_unit: SpeedUnit::MilesPerHour,
+                };
+                f(&mut period, value);
+                period
+            })
+            .collect();
+        WeatherForecast {
+            periods,
+            highs: HashMap::new(),
+        }
+    }
+
+    /// A day of steady rain, with amounts and UV. Gives the chart every row
+    /// it can have
+    pub(crate) fn rainy_day() -> WeatherForecast {
+        hourly(&[(); 24], |period, _| {
+            period.probability_of_precipitation = Some(80.0);
+            period.precipitation_amount = Some(0.1);
+            period.condition = Condition::Rain;
+            period.uv_index = Some(5.0);
+        })
+    }
+
+    /// A forecast with a single period starting at `start_time`
+    pub(crate) fn current(
+        start_time: DateTime<Utc>,
+        temperature: f64,
+        unit: TemperatureUnit,
+    ) -> WeatherForecast {
+        let mut forecast = hourly(&[temperature], |period, temperature| {
+            period.temperature = *temperature;
+            period.temperature_unit = unit;
+        });
+        forecast.periods[0].start_time = start_time;
+        forecast.periods[0].end_time = start_time + TimeDelta::hours(1);
+        forecast
+    }
+
+    /// Build an hourly forecast from a list of PoPs
+    fn forecast(pops: &[f64]) -> WeatherForecast {
+        hourly(pops, |period, pop| {
+            period.probability_of_precipitation = Some(*pop);
+            if *pop >= 50.0 {
+                period.condition = Condition::Rain;
+            }
+        })
+    }
+
+    /// 8am in Boston
+    pub(super) fn hour(i: i64) -> DateTime<Utc> {
+        let start: DateTime<Utc> = "2025-06-01T12:00:00Z".parse().unwrap();
+        start + TimeDelta::hours(i)
+    }
+
+    const BOSTON: Coordinates = Coordinates {
+        latitude: 42.36,
+        longitude: -71.06,
+    };
+
+    #[test]
+    fn test_outside_penalty() {
+        let penalty = |temperature, pop, uv_index| {
+            let forecast = hourly(&[()], |period, _| {
+                period.temperature = temperature;
+                period.probability_of_precipitation = Some(pop);
+                period.uv_index = uv_index;
+            });
+            forecast.periods[0].outside_penalty()
+        };
+        // Comfortable, dry, and not too sunny is perfect
+        assert_eq!(penalty(60.0, 0.0, None), 0.0);
+        assert_eq!(penalty(78.0, 0.0, Some(5.0)), 0.0);
+        // 1 per degree outside the comfortable range
+        assert_eq!(penalty(50.0, 0.0, None), 10.0);
+        assert_eq!(penalty(88.0, 0.0, None), 10.0);
+        // 1 per 2% chance of rain
+        assert_eq!(penalty(70.0, 40.0, None), 20.0);
+        // 5 per UV point over 5
+        assert_eq!(penalty(70.0, 0.0, Some(7.0)), 10.0);
+        assert_eq!(penalty(50.0, 40.0, Some(7.0)), 40.0);
+    }
+
+    /// Comfort is judged in °F regardless of units
+    #[test]
+    fn test_outside_penalty_celsius() {
+        let forecast = hourly(&[21.0, 10.0], |period, temperature| {
+            period.temperature = *temperature;
+            period.temperature_unit = TemperatureUnit::Celsius;
+        });
+        assert_eq!(forecast.periods[0].outside_penalty(), 0.0);
+        assert_eq!(forecast.periods[1].outside_penalty(), 10.0);
+    }
+
+    #[test]
+    fn test_best_time_outside() {
+        let mut temps = [85.0; 24];
+        temps[3] = 82.0;
+        temps[4] = 70.0;
+        temps[5] = 82.0;
+        // Nicer still, but it's 11pm
+        temps[15] = 65.0;
+        let forecast = hourly(&temps, |period, temperature| {
+            period.temperature = *temperature;
+        });
+        let window = forecast.best_time_outside(BOSTON).unwrap();
+        // Neighbors within 5 of the best hour are included
+        assert_eq!(window.start.to_utc(), hour(3));
+        assert_eq!(window.end.to_utc(), hour(6));
+        assert_eq!(window.best.temp(), 70.0);
+    }
+
+    /// Rain and UV can rule out an otherwise nice hour
+    #[test]
+    fn test_best_time_outside_rain_uv() {
+        let forecast = hourly(&[(0.0, 9.0), (80.0, 2.0), (10.0, 2.0)], {
+            |period, (pop, uv_index)| {
+                period.temperature = 70.0;
+                period.probability_of_precipitation = Some(*pop);
+                period.uv_index = Some(*uv_index);
+            }
+        });
+        let window = forecast.best_time_outside(BOSTON).unwrap();
+        assert_eq!(window.start.to_utc(), hour(2));
+        assert_eq!(window.end.to_utc(), hour(3));
+    }
+
+    /// Nighttime hours are never suggested
+    #[test]
+    fn test_best_time_outside_night() {
+        let forecast = hourly(&[(); 24], |_, _| {});
+        let night = WeatherForecast {
+            periods: forecast.periods[14..20].to_vec(),
+            highs: HashMap::new(),
+        };
+        assert!(night.best_time_outside(BOSTON).is_none());
+    }
+
+    /// The provider's full-day high covers hours that have already passed
+    #[test]
+    fn test_high() {
+        let mut forecast = forecast(&[0.0; 36]);
+        forecast.periods[0].temperature = 70.0;
+        // Periods are bucketed by local date, so take the dates from them
+        // rather than assuming a timezone. 36 hours always spans two dates
+        let first = forecast.periods[0].start_time().date_naive();
+        let last = forecast.periods[35].start_time().date_naive();
+        assert_eq!(forecast.high(first), Some(70.0));
+        assert_eq!(forecast.high(last), Some(60.0));
+        assert_eq!(forecast.high(last.succ_opt().unwrap()), None);
+
+        forecast.highs.insert(first, 75.0);
+        forecast.highs.insert(last, 55.0);
+        assert_eq!(forecast.high(first), Some(75.0));
+        assert_eq!(forecast.high(last), Some(60.0));
+    }
+
+    #[test]
+    fn test_precipitation_event_none() {
+        assert_eq!(forecast(&[]).precipitation_event(50.0), None);
+        assert_eq!(
+            forecast(&[0.0, 10.0, 49.0, 20.0]).precipitation_event(50.0),
+            None
+        );
+    }
+
+    #[test]
+    fn test_precipitation_event_later() {
+        let event = forecast(&[10.0, 20.0, 60.0, 80.0, 70.0, 30.0, 90.0])
+            .precipitation_event(50.0)
+            .unwrap();
+        assert_eq!(
+            event,
+            PrecipitationEvent {
+                condition: Condition::Rain,
+                start: hour(2),
+                end: Some(hour(5)),
+                peak: 80.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_precipitation_event_ongoing() {
+        let event = forecast(&[70.0, 55.0, 10.0])
+            .precipitation_event(50.0)
+            .unwrap();
+        assert_eq!(event.start, hour(0));
+        assert_eq!(event.end, Some(hour(2)));
+        assert_eq!(event.peak, 70.0);
+    }
+
+    /// If the event lasts through the end of the window, we don't know when
+    /// it ends
+    #[test]
+    fn test_precipitation_event_open_ended() {
+        let event = forecast(&[10.0, 60.0, 60.0])
+            .precipitation_event(50.0)
+            .unwrap();
+        assert_eq!(event.start, hour(1));
+        assert_eq!(event.end, None);
+
+        // Only the next 24 hours are considered
+        let mut pops = vec![60.0; 30];
+        pops[..3].fill(0.0);
+        let event = forecast(&pops).precipitation_event(50.0).unwrap();
+        assert_eq!(event.start, hour(3));
+        assert_eq!(event.end, None);
     }
 
-    /// Formatted probability of precipitation
-    pub fn prob_of_precip(&self) -> String {
-        format!(
-            "{:.0}%",
-            self.probability_of_precipitation.value.unwrap_or_default()
-        )
+    #[test]
+    fn test_precipitation_event_threshold() {
+        let forecast = forecast(&[10.0, 30.0, 40.0, 10.0]);
+        assert_eq!(forecast.precipitation_event(50.0), None);
+        let event = forecast.precipitation_event(30.0).unwrap();
+        assert_eq!(event.start, hour(1));
+        assert_eq!(event.end, Some(hour(3)));
+        assert_eq!(event.peak, 40.0);
+        assert_eq!(event.condition, Condition::Cloudy);
     }
 }
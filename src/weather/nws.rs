@@ -0,0 +1,363 @@
+use crate::{
+    units::{TemperatureUnit, Units},
+    util::http_get,
+    weather::{Condition, ForecastPeriod, WeatherForecast, WeatherProvider},
+};
+use chrono::{DateTime, Local, NaiveDate, TimeDelta, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+const API_HOST: &str = "https://api.weather.gov";
+
+/// National Weather Service. US only
+///
+/// https://www.weather.gov/documentation/services-web-api
+#[derive(Debug)]
+pub struct Nws {
+    /// Raw gridpoint data, which has some values the forecast doesn't
+    gridpoint_url: String,
+    /// Hourly forecast
+    url: String,
+    units: Units,
+}
+
+impl Nws {
+    pub fn new(office: &str, gridpoint: (u32, u32), units: Units) -> Self {
+        let gridpoint_url = format!(
+            "{}/gridpoints/{}/{},{}",
+            API_HOST, office, gridpoint.0, gridpoint.1,
+        );
+        let url = format!(
+            "{}/forecast/hourly?units={}",
+            gridpoint_url,
+            // NWS converts for us, so we can display whatever we get
+            match units {
+                Units::Imperial => "us",
+                Units::Metric => "si",
+            }
+        );
+        Self {
+            gridpoint_url,
+            url,
+            units,
+        }
+    }
+
+    /// Get full-day highs from the raw gridpoint data. The hourly forecast
+    /// starts at the current hour, so it doesn't know today's high once
+    /// it's passed
+    fn highs(
+        &self,
+        gridpoint: &GridpointProperties,
+    ) -> HashMap<NaiveDate, f64> {
+        let unit = self.units.temperature();
+        gridpoint
+            .max_temperature
+            .values
+            .iter()
+            .filter_map(|value| {
+                // Each high covers the daytime hours of one day
+                let (start, _) = value.interval()?;
+                let high = TemperatureUnit::Celsius.to_fahrenheit(value.value?);
+                Some((
+                    start.with_timezone(&Local).date_naive(),
+                    unit.convert_from_fahrenheit(high),
+                ))
+            })
+            .collect()
+    }
+}
+
+impl WeatherProvider for Nws {
+    fn fetch(&self) -> Result<WeatherForecast, ()> {
+        let response: ApiForecast = http_get(&self.url)?;
+        // Raw data is only for gusts and highs, so go on without it if need be
+        let gridpoint = http_get::<ApiGridpoint>(&self.gridpoint_url)
+            .ok()
+            .map(|gridpoint| gridpoint.properties);
+        let speed_unit = self.units.speed();
+        let periods = response
+            .properties
+            .periods
+            .into_iter()
+            .map(|period| ForecastPeriod {
+                start_time: period.start_time,
+                end_time: period.end_time,
+                temperature: period.temperature,
+                temperature_unit: period.temperature_unit,
+                probability_of_precipitation: period
+                    .probability_of_precipitation
+                    .value,
+                // The hourly forecast doesn't include amounts
+                precipitation_amount: None,
+                precipitation_unit: self.units.precipitation(),
+                condition: condition(&period.short_forecast, &period.icon),
+                uv_index: None,
+                // Gusts are only in the raw gridpoint data, in km/h
+                wind_gust: gridpoint
+                    .as_ref()
+                    .and_then(|gridpoint| {
+                        gridpoint.wind_gust.at(period.start_time)
+                    })
+                    .map(|gust| {
+                        speed_unit.convert_from_kilometers_per_hour(gust)
+                    }),
+                speed_unit,
+            })
+            .collect();
+        let highs = gridpoint
+            .map(|gridpoint| self.highs(&gridpoint))
+            .unwrap_or_default();
+        Ok(WeatherForecast { periods, highs })
+    }
+}
+
+/// Boil down the forecast text into a condition. The text is pretty
+/// consistent, but if we don't recognize it then fall back to the code in the
+/// icon URL.
+///
+/// https://api.weather.gov/icons
+fn condition(short_forecast: &str, icon: &str) -> Condition {
+    let text = short_forecast.to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|word| text.contains(word));
+    // Check most notable first, since text can mention multiple conditions
+    if has(&["thunder", "t-storm"]) {
+        Condition::Thunder
+    } else if has(&["snow", "flurries", "sleet", "blizzard", "ice"]) {
+        Condition::Snow
+    } else if has(&["rain", "showers", "drizzle"]) {
+        Condition::Rain
+    } else if has(&["fog", "haze", "smoke", "dust"]) {
+        Condition::Fog
+    } else if has(&["partly", "mostly sunny", "mostly clear"]) {
+        Condition::PartlyCloudy
+    } else if has(&["cloudy", "overcast"]) {
+        Condition::Cloudy
+    } else if has(&["sunny", "clear"]) {
+        Condition::Clear
+    } else {
+        // The code is the path segment after day/night, minus the PoP suffix
+        let code = icon
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .split('/')
+            .skip_while(|segment| !matches!(*segment, "day" | "night"))
+            .nth(1)
+            .and_then(|segment| segment.split(',').next())
+            .unwrap_or_default();
+        match code {
+            "skc" | "few" | "hot" | "cold" | "wind_skc" | "wind_few" => {
+                Condition::Clear
+            }
+            "sct" | "wind_sct" => Condition::PartlyCloudy,
+            "bkn" | "ovc" | "wind_bkn" | "wind_ovc" => Condition::Cloudy,
+            "fog" | "haze" | "smoke" | "dust" => Condition::Fog,
+            "rain" | "rain_showers" | "rain_showers_hi" | "fzra"
+            | "rain_fzra" => Condition::Rain,
+            "snow" | "rain_snow" | "rain_sleet" | "snow_sleet"
+            | "snow_fzra" | "sleet" | "blizzard" => Condition::Snow,
+            "tsra" | "tsra_sct" | "tsra_hi" | "tornado" | "hurricane"
+            | "tropical_storm" => Condition::Thunder,
+            _ => Condition::Unknown,
+        }
+    }
+}
+
+/// Weather is a phenomenon where food and fruit and shit falls from the sky
+///
+/// https://www.weather.gov/documentation/services-web-api#/default/gridpoint_forecast
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiForecast {
+    properties: ForecastProperties,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ForecastProperties {
+    periods: Vec<ApiPeriod>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiPeriod {
+    start_time: DateTime<Utc>,
+    end_time: DateTime<Utc>,
+    temperature: f64,
+    temperature_unit: TemperatureUnit,
+    probability_of_precipitation: Unit,
+    /// e.g. "Chance Rain Showers"
+    short_forecast: String,
+    /// e.g. "https://api.weather.gov/icons/land/day/rain_showers,30?size=small"
+    icon: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Unit {
+    value: Option<f64>,
+}
+
+/// Raw forecast data for a gridpoint. Each value is a time series
+///
+/// https://www.weather.gov/documentation/services-web-api#/default/gridpoint
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiGridpoint {
+    properties: GridpointProperties,
+}
+
+/// Raw data is always in SI units, regardless of what the forecast uses
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GridpointProperties {
+    /// °C
+    max_temperature: Layer,
+    /// km/h
+    wind_gust: Layer,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Layer {
+    values: Vec<LayerValue>,
+}
+
+impl Layer {
+    /// Get the value for the interval containing a time
+    fn at(&self, time: DateTime<Utc>) -> Option<f64> {
+        self.values
+            .iter()
+            .find(|value| {
+                value
+                    .interval()
+                    .is_some_and(|(start, end)| start <= time && time < end)
+            })?
+            .value
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LayerValue {
+    /// Start time and ISO 8601 duration, e.g.
+    /// "2025-06-01T11:00:00+00:00/PT13H"
+    valid_time: String,
+    value: Option<f64>,
+}
+
+impl LayerValue {
+    /// Parse the start and end of the valid time
+    fn interval(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let (start, duration) = self.valid_time.split_once('/')?;
+        let start: DateTime<Utc> = start.parse().ok()?;
+        Some((start, start + parse_duration(duration)?))
+    }
+}
+
+/// Parse an ISO 8601 duration, e.g. "P1DT6H". NWS only uses days, hours, and
+/// minutes
+fn parse_duration(duration: &str) -> Option<TimeDelta> {
+    let mut total = TimeDelta::zero();
+    let mut number = String::new();
+    // Designators after the T are for time, e.g. M is minutes, not months
+    let mut time = false;
+    for c in duration.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => time = true,
+            designator => {
+                let value: i64 = number.parse().ok()?;
+                number.clear();
+                total += match (designator, time) {
+                    ('D', false) => TimeDelta::days(value),
+                    ('H', true) => TimeDelta::hours(value),
+                    ('M', true) => TimeDelta::minutes(value),
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("PT13H"), Some(TimeDelta::hours(13)));
+        assert_eq!(parse_duration("P1DT6H"), Some(TimeDelta::hours(30)));
+        assert_eq!(parse_duration("P2D"), Some(TimeDelta::days(2)));
+        assert_eq!(parse_duration("PT1H30M"), Some(TimeDelta::minutes(90)));
+        assert_eq!(parse_duration("P1M"), None);
+        assert_eq!(parse_duration("13H"), None);
+    }
+
+    #[test]
+    fn test_layer_at() {
+        let layer = Layer {
+            values: vec![
+                LayerValue {
+                    valid_time: "2025-06-01T12:00:00+00:00/PT2H".into(),
+                    value: Some(30.0),
+                },
+                LayerValue {
+                    valid_time: "2025-06-01T14:00:00+00:00/PT1H".into(),
+                    value: Some(50.0),
+                },
+            ],
+        };
+        let at = |time: &str| layer.at(time.parse().unwrap());
+        assert_eq!(at("2025-06-01T11:00:00Z"), None);
+        assert_eq!(at("2025-06-01T13:00:00Z"), Some(30.0));
+        assert_eq!(at("2025-06-01T14:00:00Z"), Some(50.0));
+        assert_eq!(at("2025-06-01T15:00:00Z"), None);
+    }
+
+    #[test]
+    fn test_condition() {
+        const ICONS: &str = "https://api.weather.gov/icons/land";
+        let cases = [
+            // Text takes priority over the icon
+            (
+                "Chance Showers And Thunderstorms",
+                "day/rain",
+                Condition::Thunder,
+            ),
+            ("Rain And Snow", "day/rain_snow", Condition::Snow),
+            ("Patchy Drizzle", "night/bkn", Condition::Rain),
+            ("Areas Of Fog", "day/fog", Condition::Fog),
+            ("Mostly Sunny", "day/few", Condition::PartlyCloudy),
+            ("Partly Cloudy", "night/sct", Condition::PartlyCloudy),
+            ("Overcast", "day/ovc", Condition::Cloudy),
+            ("Clear", "night/skc", Condition::Clear),
+            // Unrecognized text falls back to the icon code
+            ("", "day/skc", Condition::Clear),
+            ("", "night/wind_few", Condition::Clear),
+            ("", "day/sct", Condition::PartlyCloudy),
+            ("", "night/bkn", Condition::Cloudy),
+            ("", "day/haze", Condition::Fog),
+            ("", "night/rain_showers,40", Condition::Rain),
+            ("", "day/fzra,100", Condition::Rain),
+            ("", "night/snow_sleet,70", Condition::Snow),
+            ("", "day/tsra_hi,20/rain,60", Condition::Thunder),
+            ("", "night/tornado", Condition::Thunder),
+            ("", "day/sct?size=medium", Condition::PartlyCloudy),
+            ("", "night/rain,30?size=small", Condition::Rain),
+            ("Frobnicating", "day/unknown,10", Condition::Unknown),
+            ("", "skc", Condition::Unknown),
+            ("", "", Condition::Unknown),
+        ];
+        for (short_forecast, icon, expected) in cases {
+            let icon = format!("{ICONS}/{icon}");
+            assert_eq!(
+                condition(short_forecast, &icon),
+                expected,
+                "{short_forecast:?} {icon}"
+            );
+        }
+    }
+}
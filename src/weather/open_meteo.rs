@@ -0,0 +1,198 @@
+use crate::{
+    config::Coordinates,
+    units::{PrecipitationUnit, SpeedUnit, TemperatureUnit, Units},
+    util::http_get,
+    weather::{Condition, ForecastPeriod, WeatherForecast, WeatherProvider},
+};
+use chrono::{DateTime, TimeDelta};
+use serde::Deserialize;
+
+const API_HOST: &str = "https://api.open-meteo.com";
+/// Number of hours to fetch, starting at the current hour
+const FORECAST_HOURS: u32 = 48;
+
+/// Open-Meteo. Global coverage, no API key required
+///
+/// https://open-meteo.com/en/docs
+#[derive(Debug)]
+pub struct OpenMeteo {
+    url: String,
+    temperature_unit: TemperatureUnit,
+    precipitation_unit: PrecipitationUnit,
+    speed_unit: SpeedUnit,
+}
+
+impl OpenMeteo {
+    pub fn new(coordinates: Coordinates, units: Units) -> Self {
+        let temperature_unit = units.temperature();
+        let precipitation_unit = units.precipitation();
+        let speed_unit = units.speed();
+        let url = format!(
+            "{API_HOST}/v1/forecast?latitude={}&longitude={}\
+            &hourly=temperature_2m,precipitation_probability,precipitation,\
+            weather_code,uv_index,wind_gusts_10m\
+            &daily=temperature_2m_max&timezone=auto\
+            &temperature_unit={}&precipitation_unit={}&wind_speed_unit={}\
+            &timeformat=unixtime\
+            &forecast_hours={FORECAST_HOURS}",
+            coordinates.latitude,
+            coordinates.longitude,
+            match temperature_unit {
+                TemperatureUnit::Fahrenheit => "fahrenheit",
+                TemperatureUnit::Celsius => "celsius",
+            },
+            match precipitation_unit {
+                PrecipitationUnit::Inches => "inch",
+                PrecipitationUnit::Millimeters => "mm",
+            },
+            match speed_unit {
+                SpeedUnit::MilesPerHour => "mph",
+                SpeedUnit::KilometersPerHour => "kmh",
+            },
+        );
+        Self {
+            url,
+            temperature_unit,
+            precipitation_unit,
+            speed_unit,
+        }
+    }
+}
+
+impl WeatherProvider for OpenMeteo {
+    fn fetch(&self) -> Result<WeatherForecast, ()> {
+        let response: ApiForecast = http_get(&self.url)?;
+        let hourly = response.hourly;
+        // Data is column-oriented. Pull out one row per hour
+        let periods = hourly
+            .time
+            .iter()
+            .enumerate()
+            .filter_map(|(i, time)| {
+                let value = |values: &[Option<f64>]| values.get(i).copied()?;
+                let start_time = DateTime::from_timestamp(*time, 0)?;
+                Some(ForecastPeriod {
+                    start_time,
+                    end_time: start_time + TimeDelta::hours(1),
+                    // Skip hours with missing data
+                    temperature: value(&hourly.temperature_2m)?,
+                    temperature_unit: self.temperature_unit,
+                    probability_of_precipitation: value(
+                        &hourly.precipitation_probability,
+                    ),
+                    precipitation_amount: value(&hourly.precipitation),
+                    precipitation_unit: self.precipitation_unit,
+                    condition: hourly
+                        .weather_code
+                        .get(i)
+                        .copied()
+                        .flatten()
+                        .map(condition)
+                        .unwrap_or_default(),
+                    uv_index: value(&hourly.uv_index),
+                    wind_gust: value(&hourly.wind_gusts_10m),
+                    speed_unit: self.speed_unit,
+                })
+            })
+            .collect();
+
+        // Daily values start at local midnight at the location
+        let daily = response.daily;
+        let highs = daily
+            .time
+            .iter()
+            .zip(daily.temperature_2m_max)
+            .filter_map(|(time, high)| {
+                let midnight = DateTime::from_timestamp(
+                    time + response.utc_offset_seconds,
+                    0,
+                )?;
+                Some((midnight.date_naive(), high?))
+            })
+            .collect();
+        Ok(WeatherForecast { periods, highs })
+    }
+}
+
+/// https://open-meteo.com/en/docs#api_form
+#[derive(Clone, Debug, Deserialize)]
+struct ApiForecast {
+    /// Offset of the location's timezone
+    utc_offset_seconds: i64,
+    hourly: ApiHourly,
+    daily: ApiDaily,
+}
+
+/// Hourly data, one array per variable. All arrays are the same length
+#[derive(Clone, Debug, Deserialize)]
+struct ApiHourly {
+    /// Unix timestamps
+    time: Vec<i64>,
+    temperature_2m: Vec<Option<f64>>,
+    precipitation_probability: Vec<Option<f64>>,
+    precipitation: Vec<Option<f64>>,
+    /// WMO weather interpretation code
+    weather_code: Vec<Option<u8>>,
+    uv_index: Vec<Option<f64>>,
+    wind_gusts_10m: Vec<Option<f64>>,
+}
+
+/// Daily aggregates, one array per variable
+#[derive(Clone, Debug, Deserialize)]
+struct ApiDaily {
+    /// Unix timestamps of local midnight
+    time: Vec<i64>,
+    temperature_2m_max: Vec<Option<f64>>,
+}
+
+/// Map a WMO weather code to a condition
+///
+/// https://open-meteo.com/en/docs#weather_variable_documentation
+fn condition(code: u8) -> Condition {
+    match code {
+        0 | 1 => Condition::Clear,
+        2 => Condition::PartlyCloudy,
+        3 => Condition::Cloudy,
+        45 | 48 => Condition::Fog,
+        51..=67 | 80..=82 => Condition::Rain,
+        71..=77 | 85 | 86 => Condition::Snow,
+        95..=99 => Condition::Thunder,
+        _ => Condition::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_condition() {
+        let cases = [
+            (0, Condition::Clear),
+            (1, Condition::Clear),
+            (2, Condition::PartlyCloudy),
+            (3, Condition::Cloudy),
+            (45, Condition::Fog),
+            (48, Condition::Fog),
+            (51, Condition::Rain),
+            (56, Condition::Rain),
+            (67, Condition::Rain),
+            (71, Condition::Snow),
+            (77, Condition::Snow),
+            (80, Condition::Rain),
+            (82, Condition::Rain),
+            (85, Condition::Snow),
+            (86, Condition::Snow),
+            (95, Condition::Thunder),
+            (99, Condition::Thunder),
+            (4, Condition::Unknown),
+            (50, Condition::Unknown),
+            (68, Condition::Unknown),
+            (100, Condition::Unknown),
+            (255, Condition::Unknown),
+        ];
+        for (code, expected) in cases {
+            assert_eq!(condition(code), expected, "{code}");
+        }
+    }
+}
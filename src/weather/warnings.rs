@@ -0,0 +1,240 @@
+use crate::{
+    notify::{Notifier, NotifierConfig},
+    weather::{ForecastPeriod, WeatherForecast},
+};
+use chrono::{DateTime, Local, Utc};
+use serde::Deserialize;
+use std::{
+    fmt::{self, Display},
+    thread,
+};
+use tracing::info;
+
+/// How many hours ahead to look for warnings
+const HOURS: usize = 24;
+
+/// Forecast thresholds that trigger a warning. Values are in the configured
+/// [Units](crate::units::Units). Any threshold left out is never checked
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct WarningConfig {
+    /// Warn when the temperature drops to or below this
+    #[serde(default)]
+    pub freeze: Option<f64>,
+    /// Warn when the temperature climbs to or above this
+    #[serde(default)]
+    pub heat: Option<f64>,
+    /// Warn when wind gusts reach this speed
+    #[serde(default)]
+    pub wind_gust: Option<f64>,
+    /// Where to send a notification when a new warning appears
+    #[serde(default)]
+    pub notifiers: Vec<NotifierConfig>,
+}
+
+/// A threshold that the forecast crosses within the next day
+#[derive(Clone, Debug, PartialEq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    /// First hour past the threshold
+    pub start: DateTime<Utc>,
+    /// Most extreme value past the threshold, formatted with its unit
+    pub extreme: String,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WarningKind {
+    Freeze,
+    Heat,
+    WindGust,
+}
+
+impl WarningConfig {
+    /// Check the next day of the forecast against each threshold. Returns at
+    /// most one warning per kind
+    pub fn evaluate(&self, forecast: &WeatherForecast) -> Vec<Warning> {
+        let periods: Vec<&ForecastPeriod> =
+            forecast.periods().take(HOURS).collect();
+        let speed_unit = forecast.speed_unit();
+        [
+            self.freeze.and_then(|threshold| {
+                // Negate so the lowest temperature is the max
+                let (start, low) = extreme(
+                    &periods,
+                    |period| Some(-period.temp()),
+                    -threshold,
+                )?;
+                Some(Warning {
+                    kind: WarningKind::Freeze,
+                    start,
+                    extreme: format!(
+                        "{:.0}{}",
+                        -low,
+                        forecast.temperature_unit()
+                    ),
+                })
+            }),
+            self.heat.and_then(|threshold| {
+                let (start, high) =
+                    extreme(&periods, |period| Some(period.temp()), threshold)?;
+                Some(Warning {
+                    kind: WarningKind::Heat,
+                    start,
+                    extreme: format!(
+                        "{high:.0}{}",
+                        forecast.temperature_unit()
+                    ),
+                })
+            }),
+            self.wind_gust.and_then(|threshold| {
+                let (start, gust) =
+                    extreme(&periods, ForecastPeriod::wind_gust, threshold)?;
+                Some(Warning {
+                    kind: WarningKind::WindGust,
+                    start,
+                    extreme: format!("{gust:.0}{speed_unit}"),
+                })
+            }),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Send a notification for each warning whose kind wasn't present in the
+    /// previous evaluation, so we don't spam on every forecast refresh.
+    /// Notifications are sent in the background, so a slow webhook or script
+    /// can't hold up the forecast
+    pub fn notify(
+        &self,
+        location: &str,
+        previous: &[Warning],
+        warnings: &[Warning],
+    ) {
+        let new_warnings = warnings.iter().filter(|warning| {
+            !previous
+                .iter()
+                .any(|previous| previous.kind == warning.kind)
+        });
+        for warning in new_warnings {
+            info!(location, ?warning, "New weather warning");
+            let message = format!("{warning} ({location})");
+            for notifier in &self.notifiers {
+                let notifier = notifier.clone();
+                let message = message.clone();
+                thread::spawn(move || notifier.notify(&message));
+            }
+        }
+    }
+}
+
+/// Find the first period where `value` reaches `threshold`, and the highest
+/// value across all periods. Returns `None` if the threshold is never reached
+fn extreme(
+    periods: &[&ForecastPeriod],
+    value: impl Fn(&ForecastPeriod) -> Option<f64>,
+    threshold: f64,
+) -> Option<(DateTime<Utc>, f64)> {
+    let start = periods
+        .iter()
+        .find(|period| value(period).is_some_and(|value| value >= threshold))?
+        .start_time;
+    let max = periods
+        .iter()
+        .filter_map(|period| value(period))
+        .max_by(f64::total_cmp)?;
+    Some((start, max))
+}
+
+impl Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Freeze => write!(f, "Freeze"),
+            Self::Heat => write!(f, "Heat"),
+            Self::WindGust => write!(f, "Gusts"),
+        }
+    }
+}
+
+/// Long-form message, for notifications
+impl Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} warning: {} starting {}",
+            self.kind,
+            self.extreme,
+            self.start.with_timezone(&Local).format("%a %-I%P"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::weather::tests::hourly;
+
+    /// Build an hourly forecast from a list of (temperature, gust) pairs
+    fn forecast(hours: &[(f64, Option<f64>)]) -> WeatherForecast {
+        hourly(hours, |period, (temperature, wind_gust)| {
+            period.temperature = *temperature;
+            period.wind_gust = *wind_gust;
+        })
+    }
+
+    fn config() -> WarningConfig {
+        WarningConfig {
+            freeze: Some(32.0),
+            heat: Some(90.0),
+            wind_gust: Some(40.0),
+            notifiers: vec![],
+        }
+    }
+
+    #[test]
+    fn test_no_warnings() {
+        let forecast = forecast(&[(50.0, Some(10.0)), (45.0, None)]);
+        assert_eq!(config().evaluate(&forecast), vec![]);
+    }
+
+    #[test]
+    fn test_freeze_and_gusts() {
+        let forecast = forecast(&[
+            (40.0, Some(20.0)),
+            (32.0, Some(45.0)),
+            (28.0, Some(50.0)),
+            (30.0, None),
+        ]);
+        let warnings = config().evaluate(&forecast);
+        let start = forecast.periods[1].start_time;
+        assert_eq!(
+            warnings,
+            vec![
+                Warning {
+                    kind: WarningKind::Freeze,
+                    start,
+                    extreme: "28°F".into(),
+                },
+                Warning {
+                    kind: WarningKind::WindGust,
+                    start,
+                    extreme: "50mph".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unconfigured_thresholds_ignored() {
+        let forecast = forecast(&[(100.0, Some(60.0))]);
+        let config = WarningConfig {
+            heat: Some(90.0),
+            ..WarningConfig::default()
+        };
+        let kinds: Vec<WarningKind> = config
+            .evaluate(&forecast)
+            .into_iter()
+            .map(|warning| warning.kind)
+            .collect();
+        assert_eq!(kinds, vec![WarningKind::Heat]);
+    }
+}